@@ -0,0 +1,26 @@
+/**
+ * Show gallery images in a lightbox instead of navigating to them.
+ *
+ * Galleries are rendered as links with `data-lightbox` set to the gallery
+ * they belong to, so without this script they still open the full image.
+ */
+export function initGalleries() {
+  const dialog = document.createElement("dialog");
+  dialog.classList.add("lightbox");
+  const image = document.createElement("img");
+  const caption = document.createElement("p");
+  dialog.append(image, caption);
+  dialog.onclick = () => dialog.close();
+  document.body.append(dialog);
+
+  const links = document.querySelectorAll<HTMLAnchorElement>("a[data-lightbox]");
+  links.forEach((link) => {
+    link.onclick = (e) => {
+      e.preventDefault();
+      image.src = link.href;
+      image.alt = link.dataset.caption ?? "";
+      caption.textContent = link.dataset.caption ?? "";
+      dialog.showModal();
+    };
+  });
+}
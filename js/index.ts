@@ -1,5 +1,6 @@
 import { CatChatbox } from "./chatbox.ts";
 import { greet } from "./console.ts";
+import { initGalleries } from "./gallery.ts";
 import { initNsfw, setNsfw } from "./nsfw";
 import { playRandomXPSound } from "./xpsounds.ts";
 
@@ -16,5 +17,6 @@ window.setNsfw = setNsfw;
 document.addEventListener("DOMContentLoaded", function onLoad() {
   customElements.define("cat-chatbox", CatChatbox);
   initNsfw();
+  initGalleries();
   greet();
 });
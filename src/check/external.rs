@@ -0,0 +1,431 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    path::Path,
+    sync::Mutex,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::{stream, StreamExt};
+use reqwest::{StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use tokio::time::{sleep_until, Instant};
+use tracing::{debug, info};
+
+use crate::diagnostics::Diagnostic;
+
+use super::{LinkSources, PageLink};
+
+/// The result of fetching an external link.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum LinkStatus {
+    /// The server responded with this status code, after following redirects.
+    Status(u16),
+
+    /// The request failed before getting a response.
+    Error(String),
+}
+
+impl LinkStatus {
+    pub fn is_ok(&self) -> bool {
+        match self {
+            LinkStatus::Status(s) => (200..400).contains(s),
+            LinkStatus::Error(_) => false,
+        }
+    }
+}
+
+impl Display for LinkStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkStatus::Status(s) => write!(f, "HTTP {s}"),
+            LinkStatus::Error(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+/// Something that can find out whether a URL works.
+#[async_trait]
+pub trait Fetcher: Send + Sync {
+    async fn fetch(&self, url: &str) -> LinkStatus;
+}
+
+#[async_trait]
+impl<F: Fetcher + ?Sized> Fetcher for Box<F> {
+    async fn fetch(&self, url: &str) -> LinkStatus {
+        (**self).fetch(url).await
+    }
+}
+
+/// Fetches links over the network.
+pub struct HttpFetcher {
+    client: reqwest::Client,
+}
+
+impl HttpFetcher {
+    pub fn new(timeout: Duration) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("seams-link-checker/", env!("CARGO_PKG_VERSION")))
+            .timeout(timeout)
+            .build()?;
+        Ok(Self { client })
+    }
+}
+
+#[async_trait]
+impl Fetcher for HttpFetcher {
+    async fn fetch(&self, url: &str) -> LinkStatus {
+        let response = match self.client.head(url).send().await {
+            // Plenty of servers don't bother implementing HEAD
+            Ok(r)
+                if [StatusCode::METHOD_NOT_ALLOWED, StatusCode::NOT_IMPLEMENTED]
+                    .contains(&r.status()) =>
+            {
+                self.client.get(url).send().await
+            }
+            r => r,
+        };
+
+        match response {
+            Ok(r) => LinkStatus::Status(r.status().as_u16()),
+            Err(e) => LinkStatus::Error(e.to_string()),
+        }
+    }
+}
+
+/// Answers with previously recorded responses, for checking links offline.
+///
+/// The recording is a YAML or JSON map from URL to either a status code or
+/// an error message.
+pub struct RecordedFetcher {
+    responses: HashMap<String, LinkStatus>,
+}
+
+impl RecordedFetcher {
+    pub fn new(responses: HashMap<String, LinkStatus>) -> Self {
+        Self { responses }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        Ok(Self::new(serde_yaml::from_reader(file)?))
+    }
+}
+
+#[async_trait]
+impl Fetcher for RecordedFetcher {
+    async fn fetch(&self, url: &str) -> LinkStatus {
+        self.responses
+            .get(url)
+            .cloned()
+            .unwrap_or_else(|| LinkStatus::Error("no recorded response".into()))
+    }
+}
+
+/// Results of previous checks, persisted between runs.
+///
+/// Only working links are reused from the cache, so broken links get
+/// rechecked every time.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct LinkCache {
+    pub entries: BTreeMap<String, CacheEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CacheEntry {
+    pub status: LinkStatus,
+    pub checked: DateTime<Utc>,
+}
+
+impl LinkCache {
+    /// Load the cache from a file, or start with an empty one if it doesn't exist.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        match std::fs::File::open(path) {
+            Ok(f) => Ok(serde_json::from_reader(f)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    fn get_fresh(&self, url: &str, max_age: chrono::Duration) -> Option<&LinkStatus> {
+        self.entries
+            .get(url)
+            .filter(|e| e.status.is_ok() && Utc::now() - e.checked < max_age)
+            .map(|e| &e.status)
+    }
+}
+
+/// Checks many external links at once, without hammering any one host.
+pub struct ExternalChecker<F> {
+    pub fetcher: F,
+
+    /// Maximum number of requests in flight.
+    pub jobs: usize,
+
+    /// Minimum time between two requests to the same host.
+    pub host_delay: Duration,
+
+    /// How long cached results are trusted for.
+    pub max_age: chrono::Duration,
+}
+
+impl<F: Fetcher> ExternalChecker<F> {
+    /// Check every URL, using and updating the cache.
+    #[tracing::instrument(skip_all)]
+    pub async fn check(
+        &self,
+        urls: impl IntoIterator<Item = String>,
+        cache: &mut LinkCache,
+    ) -> BTreeMap<String, LinkStatus> {
+        let mut results = BTreeMap::new();
+        let mut to_fetch = vec![];
+        for url in urls {
+            match cache.get_fresh(&url, self.max_age) {
+                Some(s) => {
+                    results.insert(url, s.clone());
+                }
+                None => to_fetch.push(url),
+            }
+        }
+        info!(
+            cached = results.len(),
+            to_fetch = to_fetch.len(),
+            "Checking external links"
+        );
+
+        let limiter = HostLimiter::new(self.host_delay);
+        let fetched = stream::iter(to_fetch)
+            .map(|url| async {
+                if let Some(host) = Url::parse(&url)
+                    .ok()
+                    .and_then(|u| u.host_str().map(String::from))
+                {
+                    limiter.wait(host).await;
+                }
+                debug!(url, "fetching");
+                let status = self.fetcher.fetch(&url).await;
+                (url, status)
+            })
+            .buffer_unordered(self.jobs.max(1))
+            .collect::<Vec<_>>()
+            .await;
+
+        let now = Utc::now();
+        for (url, status) in fetched {
+            cache.entries.insert(
+                url.clone(),
+                CacheEntry {
+                    status: status.clone(),
+                    checked: now,
+                },
+            );
+            results.insert(url, status);
+        }
+
+        results
+    }
+}
+
+/// Hands out evenly spaced time slots for requests to each host.
+struct HostLimiter {
+    delay: Duration,
+    next: Mutex<HashMap<String, Instant>>,
+}
+
+impl HostLimiter {
+    fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            next: Default::default(),
+        }
+    }
+
+    async fn wait(&self, host: String) {
+        let slot = {
+            let mut next = self.next.lock().unwrap();
+            let now = Instant::now();
+            let slot = next.get(&host).map_or(now, |t| (*t).max(now));
+            next.insert(host, slot + self.delay);
+            slot
+        };
+        sleep_until(slot).await;
+    }
+}
+
+/// Whether a link points to somewhere outside of the site that we can check.
+pub fn is_external(href: &str) -> bool {
+    href.starts_with("http://") || href.starts_with("https://")
+}
+
+/// Broken external links, and where they came from.
+#[derive(Default, Debug)]
+pub struct ExternalLinkReport {
+    pub broken: BTreeMap<String, (LinkStatus, Vec<String>)>,
+}
+
+impl ExternalLinkReport {
+    pub fn new(
+        sources: &LinkSources,
+        links: &[PageLink],
+        results: &BTreeMap<String, LinkStatus>,
+    ) -> Self {
+        let mut report = Self::default();
+        for l in links {
+            let Some(status) = results.get(&l.href).filter(|s| !s.is_ok()) else {
+                continue;
+            };
+            let source = sources.source_of(l);
+            let (_, found_in) = report
+                .broken
+                .entry(l.href.clone())
+                .or_insert_with(|| (status.clone(), vec![]));
+            if !found_in.contains(&source) {
+                found_in.push(source);
+            }
+        }
+        report
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.broken.is_empty()
+    }
+
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.broken
+            .iter()
+            .flat_map(|(url, (status, sources))| {
+                sources
+                    .iter()
+                    .map(move |s| Diagnostic::error(s, format!("broken link {url:?} ({status})")))
+            })
+            .collect()
+    }
+}
+
+impl Display for ExternalLinkReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (url, (status, sources)) in &self.broken {
+            writeln!(f, "Broken link {url:?} ({status}), found in:")?;
+            for s in sources {
+                writeln!(f, "  {s}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    fn checker<F>(fetcher: F) -> ExternalChecker<F> {
+        ExternalChecker {
+            fetcher,
+            jobs: 4,
+            host_delay: Duration::ZERO,
+            max_age: chrono::Duration::hours(1),
+        }
+    }
+
+    /// Serve canned responses on localhost: 200 for `/ok`, 404 for anything else.
+    fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(&stream);
+                reader.read_line(&mut request_line).unwrap();
+                while reader.read_line(&mut String::new()).unwrap() > 2 {}
+
+                let status = match request_line.split(' ').nth(1) {
+                    Some("/ok") => "200 OK",
+                    _ => "404 Not Found",
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                )
+                .unwrap();
+            }
+        });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    pub async fn checks_links_against_local_server() {
+        let base = serve();
+        let checker = ExternalChecker {
+            host_delay: Duration::from_millis(50),
+            ..checker(HttpFetcher::new(Duration::from_secs(5)).unwrap())
+        };
+        let urls = ["/ok", "/missing", "/ok?again"].map(|p| format!("{base}{p}"));
+
+        let start = Instant::now();
+        let results = checker.check(urls.clone(), &mut LinkCache::default()).await;
+
+        assert_eq!(results[&urls[0]], LinkStatus::Status(200));
+        assert_eq!(results[&urls[1]], LinkStatus::Status(404));
+        assert!(
+            start.elapsed() >= Duration::from_millis(100),
+            "requests to the same host should be spaced out"
+        );
+    }
+
+    #[tokio::test]
+    pub async fn uses_recorded_responses_and_cache() {
+        let recorded: HashMap<String, LinkStatus> = serde_yaml::from_str(
+            r#"
+            https://example.com/: 200
+            https://example.com/gone: 410
+            https://down.example.com/: connection refused
+            "#,
+        )
+        .unwrap();
+        let mut cache = LinkCache::default();
+        cache.entries.insert(
+            "https://cached.example.com/".into(),
+            CacheEntry {
+                status: LinkStatus::Status(200),
+                checked: Utc::now(),
+            },
+        );
+
+        let results = checker(RecordedFetcher::new(recorded))
+            .check(
+                [
+                    "https://example.com/",
+                    "https://example.com/gone",
+                    "https://down.example.com/",
+                    "https://cached.example.com/",
+                ]
+                .map(String::from),
+                &mut cache,
+            )
+            .await;
+
+        assert!(results["https://example.com/"].is_ok());
+        assert_eq!(results["https://example.com/gone"], LinkStatus::Status(410));
+        assert_eq!(
+            results["https://down.example.com/"],
+            LinkStatus::Error("connection refused".into())
+        );
+        assert!(results["https://cached.example.com/"].is_ok());
+        assert_eq!(cache.entries.len(), 4);
+    }
+}
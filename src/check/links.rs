@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+
+use vfs::VfsPath;
+
+use crate::{diagnostics::Diagnostic, templates::EXTERNAL_ASSETS};
+
+use super::{find_page_links, LinkSources, PageLink};
+
+/// Find every relative or root-relative link in the rendered output that
+/// does not resolve to a generated file.
+#[tracing::instrument(skip_all, fields(out = out.as_str()))]
+pub fn find_dead_links(out: &VfsPath) -> anyhow::Result<Vec<PageLink>> {
+    let mut dead = vec![];
+
+    for link in find_page_links(out)? {
+        let Some(target) = resolve_link(&link.page, &link.href) else {
+            continue;
+        };
+        if !target_exists(out, &target)? {
+            dead.push(link);
+        }
+    }
+
+    Ok(dead)
+}
+
+/// Resolve a link found in the given page into an absolute path in the output,
+/// or [None] if the link does not point into the output at all.
+fn resolve_link(page: &str, href: &str) -> Option<String> {
+    let href = href.trim();
+    let path = href.split(['#', '?']).next().unwrap_or_default();
+
+    let is_external = path.starts_with("//")
+        || path
+            .split_once(':')
+            .is_some_and(|(scheme, _)| !scheme.contains('/'));
+    if path.is_empty() || is_external {
+        return None;
+    }
+
+    let joined = match path.strip_prefix('/') {
+        Some(_) => path.to_owned(),
+        None => {
+            let dir = page.rsplit_once('/').map_or("", |(dir, _)| dir);
+            format!("{dir}/{path}")
+        }
+    };
+
+    let mut segments: Vec<&str> = vec![];
+    for s in joined.split('/') {
+        match s {
+            "" | "." => (),
+            ".." => {
+                segments.pop();
+            }
+            s => segments.push(s),
+        }
+    }
+
+    Some(percent_decode(&format!("/{}", segments.join("/"))))
+}
+
+fn target_exists(out: &VfsPath, target: &str) -> anyhow::Result<bool> {
+    if EXTERNAL_ASSETS.contains(&target) {
+        return Ok(true);
+    }
+
+    let path = out.join(target.trim_start_matches('/'))?;
+    if path.is_file()? {
+        return Ok(true);
+    }
+
+    Ok(path.is_dir()? && path.join("index.html")?.is_file()?)
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let decoded = (bytes[i] == b'%')
+            .then(|| s.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match decoded {
+            Some(b) => {
+                out.push(b);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Dead links, grouped by what they point to and where they came from.
+#[derive(Default, Debug)]
+pub struct DeadLinkReport {
+    /// Dead link to the human-readable sources it was found in.
+    pub sources: BTreeMap<String, Vec<String>>,
+}
+
+impl DeadLinkReport {
+    /// Group dead links by href, and attribute them to the documents or
+    /// settings files that they came from.
+    pub fn new(sources: &LinkSources, dead: Vec<PageLink>) -> Self {
+        let mut report = Self::default();
+        for d in dead {
+            let source = sources.source_of(&d);
+            let entry = report.sources.entry(d.href).or_default();
+            if !entry.contains(&source) {
+                entry.push(source);
+            }
+        }
+        report
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.sources
+            .iter()
+            .flat_map(|(href, sources)| {
+                sources
+                    .iter()
+                    .map(move |s| Diagnostic::error(s, format!("dead link {href:?}")))
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for DeadLinkReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (href, sources) in &self.sources {
+            writeln!(f, "Dead link {href:?}, found in:")?;
+            for s in sources {
+                writeln!(f, "  {s}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vfs::MemoryFS;
+
+    use super::*;
+
+    #[test]
+    pub fn resolves_relative_and_root_relative_links() {
+        let page = "/blog/index.html";
+
+        assert_eq!(resolve_link(page, "/projects#x"), Some("/projects".into()));
+        assert_eq!(
+            resolve_link(page, "foo/../bar.png"),
+            Some("/blog/bar.png".into())
+        );
+        assert_eq!(resolve_link(page, "../a%20b.png"), Some("/a b.png".into()));
+        assert_eq!(resolve_link(page, "https://example.com/"), None);
+        assert_eq!(resolve_link(page, "mailto:someone@example.com"), None);
+        assert_eq!(resolve_link(page, "//cdn.example.com/x.js"), None);
+        assert_eq!(resolve_link(page, "#top"), None);
+    }
+
+    #[test]
+    pub fn finds_dead_links() -> anyhow::Result<()> {
+        let out = VfsPath::new(MemoryFS::new());
+        out.join("blog")?.create_dir_all()?;
+        out.join("blog/index.html")?.create_file()?.write_all(
+            br#"<a href="/">home</a> <a href="/blog">blog</a> <img src="/static/x.png">
+            <a href='../nowhere'>dead</a> <link href="/styles.css">"#,
+        )?;
+        out.join("index.html")?.create_file()?.write_all(b"")?;
+
+        let dead = find_dead_links(&out)?;
+
+        assert_eq!(
+            dead,
+            vec![
+                PageLink {
+                    page: "/blog/index.html".into(),
+                    href: "/static/x.png".into(),
+                },
+                PageLink {
+                    page: "/blog/index.html".into(),
+                    href: "../nowhere".into(),
+                },
+            ]
+        );
+
+        Ok(())
+    }
+}
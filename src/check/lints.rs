@@ -0,0 +1,174 @@
+use chrono::{DateTime, Utc};
+use vfs::VfsPath;
+
+use crate::{diagnostics::Diagnostic, load::document::FullyLoadedDocument, model::SiteData};
+
+/// Run every lint over the loaded site.
+pub fn lint_site(sd: &SiteData, content: &VfsPath, now: DateTime<Utc>) -> Vec<Diagnostic> {
+    let mut lints = vec![];
+    let mut lint = |path: &VfsPath, message: String| {
+        let s = path.as_str();
+        lints.push(Diagnostic::warning(
+            s.strip_prefix(content.as_str()).unwrap_or(s),
+            message,
+        ))
+    };
+
+    for p in &sd.posts {
+        let path = &p.document.path;
+        let meta = p.meta();
+        if meta.tags.is_empty() {
+            lint(path, "post has no tags".into());
+        }
+        if meta.date.published > now {
+            lint(
+                path,
+                format!(
+                    "post is published in the future, at {}",
+                    meta.date.published
+                ),
+            );
+        }
+        if let Some(dir) = post_dir_name(content, path) {
+            if dir != meta.slug.name {
+                lint(
+                    path,
+                    format!(
+                        "post is in a directory named {dir:?}, but its slug is {:?}",
+                        meta.slug.name
+                    ),
+                );
+            }
+        }
+        missing_alt_text(p, &mut lint);
+    }
+
+    for p in &sd.projects {
+        if p.meta().tags.is_empty() {
+            lint(&p.document.path, "project has no tags".into());
+        }
+        missing_alt_text(p, &mut lint);
+    }
+
+    for p in &sd.pages {
+        missing_alt_text(p, &mut lint);
+    }
+
+    for p in &sd.computers {
+        missing_alt_text(p, &mut lint);
+    }
+
+    if let Some(id) = &sd.stats.homepage_counter {
+        if !sd.stats.counters.iter().any(|c| &c.id == id) {
+            lint(
+                &content.join("settings").unwrap_or_else(|_| content.clone()),
+                format!("homepage counter {id:?} is not one of the stats counters"),
+            );
+        }
+    }
+
+    lints
+}
+
+fn missing_alt_text<M>(doc: &FullyLoadedDocument<M>, lint: &mut impl FnMut(&VfsPath, String)) {
+    let missing = doc
+        .html()
+        .split('<')
+        .filter(|tag| {
+            tag.starts_with("img ") && (!tag.contains(" alt=") || tag.contains(" alt=\"\""))
+        })
+        .count();
+    if missing > 0 {
+        lint(
+            &doc.document.path,
+            format!("{missing} image(s) have no alt text"),
+        );
+    }
+}
+
+/// The name of the directory a post is in, if it follows the
+/// `blog/YYYY/MM/DD/N/slug` layout.
+fn post_dir_name(content: &VfsPath, path: &VfsPath) -> Option<String> {
+    let dir = path.parent();
+    let rel = dir.as_str().strip_prefix(content.as_str())?;
+    let segments = rel.strip_prefix("/blog/")?.split('/').collect::<Vec<_>>();
+    (segments.len() == 5).then(|| segments[4].to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use vfs::MemoryFS;
+
+    use crate::media::MediaRegistry;
+
+    use super::*;
+
+    #[tokio::test]
+    pub async fn lints_example_site() {
+        let content = VfsPath::new(MemoryFS::new());
+        let write = |path: &str, body: &str| {
+            let p = content.join(path).unwrap();
+            p.parent().create_dir_all().unwrap();
+            p.create_file().unwrap().write_all(body.as_bytes()).unwrap();
+        };
+        write(
+            "blog/2023/01/06/0/libvirt/index.md",
+            "---\ntitle: Libvirt\nslug:\n  date: 2023-01-07\n  name: android-libvirt\ndate:\n  created: 2023-01-06T17:03:17-08:00\n  published: 2099-01-06T17:03:17-08:00\n---\n\n![](./x.png)",
+        );
+        write("blog/2023/01/06/0/libvirt/x.png", "");
+        write(
+            "pages/ok.md",
+            "---\ntitle: Ok\ntags: []\nslug: /ok\n---\n\n![a cat](./x.png)",
+        );
+        write("pages/x.png", "");
+        for dir in ["projects", "computers", "settings"] {
+            content.join(dir).unwrap().create_dir_all().unwrap();
+        }
+
+        let media = MediaRegistry::new("/static".into(), VfsPath::new(MemoryFS::new()));
+        let sd = SiteData::load(content.clone(), &media).await.unwrap();
+        let lints = lint_site(&sd, &content, Utc::now());
+
+        let path = "/blog/2023/01/06/0/libvirt/index.md";
+        let messages = lints
+            .iter()
+            .inspect(|l| assert_eq!(l.path, path))
+            .map(|l| l.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "post has no tags",
+                "post is published in the future, at 2099-01-06 17:03:17 -08:00",
+                "post is in a directory named \"libvirt\", but its slug is \"android-libvirt\"",
+                "1 image(s) have no alt text",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    pub async fn lints_missing_homepage_counter() {
+        let content = VfsPath::new(MemoryFS::new());
+        for dir in ["blog", "pages", "projects", "computers", "settings"] {
+            content.join(dir).unwrap().create_dir_all().unwrap();
+        }
+        content
+            .join("settings/stats.stats.yml")
+            .unwrap()
+            .create_file()
+            .unwrap()
+            .write_all(b"homepage_counter: sweras\ncounters:\n  - id: swears\n    title: Swears\n    terms: [heck]\n")
+            .unwrap();
+
+        let media = MediaRegistry::new("/static".into(), VfsPath::new(MemoryFS::new()));
+        let sd = SiteData::load(content.clone(), &media).await.unwrap();
+        let lints = lint_site(&sd, &content, Utc::now());
+
+        assert_eq!(lints.len(), 1, "lints = {lints:?}");
+        assert_eq!(lints[0].path, "/settings");
+        assert_eq!(
+            lints[0].message,
+            "homepage counter \"sweras\" is not one of the stats counters"
+        );
+    }
+}
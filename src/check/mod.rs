@@ -0,0 +1,240 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use tracing::debug;
+use vfs::VfsPath;
+
+use crate::model::{RouteSource, RouteTable, SiteData};
+
+pub mod external;
+pub mod links;
+pub mod lints;
+
+/// Exit code for when `seams check` found errors.
+pub const EXIT_ERRORS: u8 = 1;
+
+/// Exit code for when `seams check` found only warnings.
+pub const EXIT_WARNINGS: u8 = 2;
+
+/// A link found in a rendered page.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PageLink {
+    /// Path of the rendered file the link was found in.
+    pub page: String,
+
+    /// The link, exactly as it was written.
+    pub href: String,
+}
+
+/// Walk the rendered output and collect every link in every HTML file.
+#[tracing::instrument(skip_all, fields(out = out.as_str()))]
+pub fn find_page_links(out: &VfsPath) -> anyhow::Result<Vec<PageLink>> {
+    let mut links = vec![];
+
+    for p in out.walk_dir()? {
+        let p = p?;
+        if !p.is_file()? || p.extension().as_deref() != Some("html") {
+            continue;
+        }
+
+        let page = p.as_str().strip_prefix(out.as_str()).unwrap_or(p.as_str());
+        debug!(page, "collecting links");
+        let html = p.read_to_string()?;
+
+        links.extend(find_link_attributes(&html).map(|href| PageLink {
+            page: page.to_owned(),
+            href: href.into_owned(),
+        }));
+    }
+
+    Ok(links)
+}
+
+/// Extract the values of every `href` and `src` attribute in a piece of HTML.
+pub fn find_link_attributes(html: &str) -> impl Iterator<Item = Cow<'_, str>> {
+    find_tags(html)
+        .into_iter()
+        .flat_map(tag_attributes)
+        .filter(|(name, _)| name.eq_ignore_ascii_case("href") || name.eq_ignore_ascii_case("src"))
+        .map(|(_, value)| html_escape::decode_html_entities(value))
+}
+
+/// The inside of every start tag, like `a href="/x"`, skipping comments and
+/// any `>` in quoted attribute values.
+fn find_tags(html: &str) -> Vec<&str> {
+    let bytes = html.as_bytes();
+    let mut tags = vec![];
+    let mut i = 0;
+
+    while let Some(start) = html[i..].find('<').map(|s| i + s) {
+        if html[start..].starts_with("<!--") {
+            i = html[start..]
+                .find("-->")
+                .map_or(html.len(), |e| start + e + 3);
+            continue;
+        }
+        i = start + 1;
+        if !bytes.get(i).is_some_and(u8::is_ascii_alphabetic) {
+            continue;
+        }
+
+        let mut quote = None;
+        let end = (i..bytes.len())
+            .find(|&j| match (quote, bytes[j]) {
+                (Some(q), c) => {
+                    if c == q {
+                        quote = None;
+                    }
+                    false
+                }
+                (None, c @ (b'"' | b'\'')) => {
+                    quote = Some(c);
+                    false
+                }
+                (None, c) => c == b'>',
+            })
+            .unwrap_or(bytes.len());
+        tags.push(&html[i..end]);
+        i = end;
+    }
+
+    tags
+}
+
+/// The names and raw values of the attributes in a tag.
+fn tag_attributes(tag: &str) -> Vec<(&str, &str)> {
+    let bytes = tag.as_bytes();
+    let is_space = |i: usize| bytes.get(i).is_some_and(u8::is_ascii_whitespace);
+    let mut attributes = vec![];
+
+    // Skip the tag name
+    let mut i = bytes
+        .iter()
+        .position(|c| c.is_ascii_whitespace() || *c == b'/')
+        .unwrap_or(bytes.len());
+    loop {
+        while is_space(i) || bytes.get(i) == Some(&b'/') {
+            i += 1;
+        }
+        if i >= bytes.len() {
+            break;
+        }
+
+        let name_start = i;
+        while i < bytes.len() && !is_space(i) && !matches!(bytes[i], b'=' | b'/') {
+            i += 1;
+        }
+        let name = &tag[name_start..i];
+        while is_space(i) {
+            i += 1;
+        }
+        if bytes.get(i) != Some(&b'=') {
+            continue;
+        }
+        i += 1;
+        while is_space(i) {
+            i += 1;
+        }
+
+        let value = match bytes.get(i) {
+            Some(&q @ (b'"' | b'\'')) => {
+                let start = i + 1;
+                let end = tag[start..]
+                    .find(q as char)
+                    .map_or(tag.len(), |e| start + e);
+                i = end + 1;
+                &tag[start..end]
+            }
+            _ => {
+                let start = i;
+                while i < bytes.len() && !is_space(i) {
+                    i += 1;
+                }
+                &tag[start..i]
+            }
+        };
+        attributes.push((name, value));
+    }
+
+    attributes
+}
+
+/// Attributes links in the rendered output to the files they were written in.
+pub struct LinkSources<'a> {
+    /// What every page was rendered from.
+    routes: &'a RouteTable,
+
+    content: VfsPath,
+
+    /// Links that show up in the chrome of every page, to the settings they came from.
+    chrome: HashMap<&'a str, &'static str>,
+}
+
+impl<'a> LinkSources<'a> {
+    pub fn new(sd: &'a SiteData, content: &VfsPath) -> Self {
+        let mut chrome: HashMap<&str, &str> = HashMap::new();
+        let mut navbar = sd.navbar.iter().collect::<Vec<_>>();
+        while let Some(item) = navbar.pop() {
+            if let Some(href) = &item.href {
+                chrome.insert(href, "/settings/*.navbar.yml");
+            }
+            navbar.extend(&item.children);
+        }
+        for b in &sd.buttons {
+            if let Some(href) = &b.href {
+                chrome.insert(href, "/settings/*.88x31.yml");
+            }
+            chrome.insert(&b.img, "/settings/*.88x31.yml");
+        }
+        for w in &sd.webrings {
+            chrome.insert(&w.prev, "/settings/*.webring.yml");
+            chrome.insert(&w.next, "/settings/*.webring.yml");
+            for href in find_link_attributes(&w.html) {
+                if let Cow::Borrowed(href) = href {
+                    chrome.insert(href, "/settings/*.webring.yml");
+                }
+            }
+        }
+
+        Self {
+            routes: &sd.routes,
+            content: content.clone(),
+            chrome,
+        }
+    }
+
+    /// Human-readable description of where a link came from.
+    pub fn source_of(&self, link: &PageLink) -> String {
+        if let Some(s) = self.chrome.get(link.href.as_str()) {
+            return s.to_string();
+        }
+
+        match self.routes.get(&link.page) {
+            Some(RouteSource::Document(p)) => {
+                let s = p.as_str();
+                s.strip_prefix(self.content.as_str())
+                    .unwrap_or(s)
+                    .to_owned()
+            }
+            _ => link.page.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn finds_link_attributes_in_any_tag_layout() {
+        let html = r#"<p><a href="/a">a</a> <img src='/b.png' alt="x > y">
+<a
+  href="/c">c</a><a	HREF="/d?x=1&amp;y=2"><a title="<b>" href=/e>
+<!-- <a href="/commented"> -->
+<div data-href="/f" class=x>a < b and c > d <a href="/g"></div>"#;
+
+        assert_eq!(
+            find_link_attributes(html).collect::<Vec<_>>(),
+            ["/a", "/b.png", "/c", "/d?x=1&y=2", "/e", "/g"]
+        );
+    }
+}
@@ -1,16 +1,51 @@
-use std::path::PathBuf;
-
-use futures::{
-    stream::{self, FuturesOrdered},
-    StreamExt,
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
 };
+
+use anyhow::Context;
+use frunk::Semigroup;
+use futures::{stream, StreamExt};
+use itertools::Itertools;
 use tokio::{
     fs::File,
     io::{AsyncReadExt, BufReader},
 };
-use tracing::{error, info};
+use tracing::{error, info, warn};
+use vfs::{MemoryFS, PhysicalFS, VfsPath};
 
-use crate::{media::Media, upload::upload_to_s3};
+use crate::{
+    check::{
+        external::{
+            is_external, ExternalChecker, ExternalLinkReport, Fetcher, HttpFetcher, LinkCache,
+            RecordedFetcher,
+        },
+        find_page_links,
+        links::{find_dead_links, DeadLinkReport},
+        lints::lint_site,
+        LinkSources, EXIT_ERRORS, EXIT_WARNINGS,
+    },
+    deploy::{deploy, DeployOptions},
+    diagnostics::Diagnostic,
+    errors::Errors,
+    gc::{find_orphans, read_rendered_text},
+    load::{
+        settings::load_settings_in_dir,
+        site_data::{SiteDataLoadError, SiteDataUserError},
+    },
+    media::{default_manifest_path, Media, MediaManifest, MediaRegistry},
+    model::{SiteData, SiteSettings, StorageSettings},
+    render::output::{build_static_site, write_static_site},
+    scaffold::{scaffold, DocumentKind},
+    schema::{add_vscode_mappings, schemas},
+    transform::{
+        remote::RemoteImages,
+        rewrite::{find_local_images, rewrite_image_urls},
+    },
+    upload::{upload_to_s3, ObjectStore, Retries, S3Config},
+};
 
 #[derive(clap::Parser)]
 pub struct TopLevel {
@@ -21,6 +56,12 @@ pub struct TopLevel {
 #[derive(clap::Subcommand)]
 pub enum Subcommand {
     Build(BuildCommand),
+    Check(CheckCommand),
+    Deploy(DeployCommand),
+    #[clap(subcommand)]
+    Media(MediaCommand),
+    New(NewCommand),
+    Schema(SchemaCommand),
     Upload(UploadCommand),
     Watch(WatchCommand),
 }
@@ -38,64 +79,705 @@ pub struct BuildCommand {
     /// Output directory
     #[clap(short, long, default_value = "out")]
     pub out: PathBuf,
+
+    /// Where to write the media manifest that `seams media gc` reads.
+    /// Defaults to `<out>.media.json`, next to the output directory
+    #[clap(long)]
+    pub media_manifest: Option<PathBuf>,
+
+    /// Upload images and other media to object storage, instead of putting
+    /// them in the output directory
+    #[clap(long)]
+    pub media_cdn: bool,
+
+    /// Download remote images referenced in content, and host copies of them
+    /// alongside the rest of the media
+    #[clap(long)]
+    pub mirror_images: bool,
+
+    /// Only use already-downloaded remote images, leaving the rest remote.
+    /// Implies --mirror-images
+    #[clap(long)]
+    pub offline: bool,
+
+    /// Where to keep downloaded remote images
+    #[clap(long, default_value = ".seams-cache/images")]
+    pub image_cache: PathBuf,
+
+    #[clap(flatten)]
+    pub storage: StorageArgs,
+}
+
+impl BuildCommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let media_cdn = match self.media_cdn {
+            true => Some(
+                self.storage
+                    .resolve_with_site(Some(self.storage.site.as_deref().unwrap_or(&self.src)))?,
+            ),
+            false => None,
+        };
+
+        let remote_images = match self.mirror_images || self.offline {
+            true => {
+                std::fs::create_dir_all(&self.image_cache)?;
+                let cache = VfsPath::new(PhysicalFS::new(&self.image_cache));
+                Some(RemoteImages::new(cache, self.offline)?)
+            }
+            false => None,
+        };
+
+        let media_manifest = match self.media_manifest {
+            Some(p) => p,
+            None => default_manifest_path(&self.out)?,
+        };
+
+        let result = build_static_site(
+            self.src,
+            self.out,
+            media_manifest,
+            self.script_assets,
+            media_cdn,
+            remote_images,
+        )
+        .await;
+
+        match result.map_err(|e| e.downcast::<SiteDataLoadError>()) {
+            Err(Ok(SiteDataLoadError::UserError(errors))) => {
+                print_user_errors(&errors, false)?;
+                anyhow::bail!("Found {} errors in content", errors.len())
+            }
+            Err(Ok(e)) => Err(e.into()),
+            Err(Err(e)) => Err(e),
+            Ok(()) => Ok(()),
+        }
+    }
+}
+
+/// Load and render the site in memory, without writing any output, and
+/// report every problem found.
+///
+/// Exits with 1 if there were errors or checking failed, and 2 if there were
+/// only warnings.
+#[derive(clap::Args)]
+pub struct CheckCommand {
+    /// Content sources directory
+    pub src: PathBuf,
+
+    /// Also check that external links work
+    #[clap(long)]
+    pub external: bool,
+
+    /// Maximum number of external links to fetch at once
+    #[clap(long, default_value = "8")]
+    pub jobs: usize,
+
+    /// Minimum time between requests to the same host, in milliseconds
+    #[clap(long, default_value = "1000")]
+    pub host_delay_ms: u64,
+
+    /// File to cache external link results in between runs
+    #[clap(long)]
+    pub cache: Option<PathBuf>,
+
+    /// How long cached external link results are trusted for, in hours
+    #[clap(long, default_value = "24")]
+    pub cache_max_age_hours: i64,
+
+    /// Use recorded responses from this YAML or JSON file instead of the network
+    #[clap(long)]
+    pub responses: Option<PathBuf>,
+
+    /// How to print problems. `json` prints one object per line, for editors
+    #[clap(long, value_enum, default_value = "human")]
+    pub message_format: MessageFormat,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageFormat {
+    Human,
+    Json,
+}
+
+fn print_diagnostic(d: &Diagnostic, source: Option<&str>, json: bool) -> anyhow::Result<()> {
+    match json {
+        true => println!("{}", serde_json::to_string(d)?),
+        false => print!("{}", d.render(source)),
+    }
+    Ok(())
+}
+
+/// Print errors in content, with snippets of the files they're in.
+fn print_user_errors(errors: &Errors<SiteDataUserError>, json: bool) -> anyhow::Result<()> {
+    for e in errors {
+        let source = e.path.read_to_string().ok();
+        for d in e.diagnostics(source.as_deref()) {
+            print_diagnostic(&d, source.as_deref(), json)?;
+        }
+    }
+    Ok(())
+}
+
+impl CheckCommand {
+    pub async fn run(self) -> anyhow::Result<ExitCode> {
+        let content = VfsPath::new(PhysicalFS::new(&self.src));
+        let out = VfsPath::new(MemoryFS::new());
+        let json = self.message_format == MessageFormat::Json;
+
+        let media = MediaRegistry::new("/static".into(), out.join("static")?);
+        let sd = match SiteData::load(content.clone(), &media).await {
+            Ok(sd) => sd,
+            Err(SiteDataLoadError::UserError(errors)) => {
+                print_user_errors(&errors, json)?;
+                error!(count = errors.len(), "Found errors in content");
+                return Ok(ExitCode::from(EXIT_ERRORS));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        write_static_site(&sd, out.clone(), vec![])?;
+
+        let sources = LinkSources::new(&sd, &content);
+        let mut fail = false;
+
+        let dead = find_dead_links(&out)?;
+        let report = DeadLinkReport::new(&sources, dead);
+        if report.is_empty() {
+            info!("No dead links found");
+        } else {
+            match json {
+                true => report
+                    .diagnostics()
+                    .iter()
+                    .try_for_each(|d| print_diagnostic(d, None, json))?,
+                false => print!("{report}"),
+            }
+            error!(count = report.sources.len(), "Found dead links");
+            fail = true;
+        }
+
+        if self.external {
+            let report = self.check_external(&sources, &out).await?;
+            if report.is_empty() {
+                info!("No broken external links found");
+            } else {
+                match json {
+                    true => report
+                        .diagnostics()
+                        .iter()
+                        .try_for_each(|d| print_diagnostic(d, None, json))?,
+                    false => print!("{report}"),
+                }
+                error!(count = report.broken.len(), "Found broken external links");
+                fail = true;
+            }
+        }
+
+        let lints = lint_site(&sd, &content, chrono::Utc::now());
+        for l in &lints {
+            print_diagnostic(l, None, json)?;
+        }
+        if !lints.is_empty() {
+            warn!(count = lints.len(), "Found warnings");
+        }
+
+        if fail {
+            return Ok(ExitCode::from(EXIT_ERRORS));
+        }
+        if !lints.is_empty() {
+            return Ok(ExitCode::from(EXIT_WARNINGS));
+        }
+
+        Ok(ExitCode::SUCCESS)
+    }
+
+    async fn check_external(
+        &self,
+        sources: &LinkSources<'_>,
+        out: &VfsPath,
+    ) -> anyhow::Result<ExternalLinkReport> {
+        let links = find_page_links(out)?
+            .into_iter()
+            .filter(|l| is_external(&l.href))
+            .collect_vec();
+        let urls = links
+            .iter()
+            .map(|l| l.href.clone())
+            .collect::<BTreeSet<_>>();
+
+        let mut cache = match &self.cache {
+            Some(p) => LinkCache::load(p)?,
+            None => LinkCache::default(),
+        };
+        let fetcher: Box<dyn Fetcher> = match &self.responses {
+            Some(p) => Box::new(RecordedFetcher::load(p)?),
+            None => Box::new(HttpFetcher::new(Duration::from_secs(30))?),
+        };
+        let checker = ExternalChecker {
+            fetcher,
+            jobs: self.jobs,
+            host_delay: Duration::from_millis(self.host_delay_ms),
+            max_age: chrono::Duration::hours(self.cache_max_age_hours),
+        };
+
+        let results = checker.check(urls, &mut cache).await;
+
+        if let Some(p) = &self.cache {
+            cache.save(p)?;
+        }
+
+        Ok(ExternalLinkReport::new(sources, &links, &results))
+    }
+}
+
+/// Sync a built site to S3-compatible object storage.
+///
+/// Environment variables required: AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY
+#[derive(clap::Args)]
+pub struct DeployCommand {
+    /// Output directory of a previous build
+    #[clap(default_value = "out")]
+    pub out: PathBuf,
+
+    /// Delete remote files that no longer exist locally
+    #[clap(long)]
+    pub delete: bool,
+
+    /// Print what would be uploaded and deleted, without doing it
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Maximum number of uploads at once
+    #[clap(long, default_value = "8")]
+    pub jobs: usize,
+
+    #[clap(flatten)]
+    pub storage: StorageArgs,
+}
+
+impl DeployCommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let config = self.storage.resolve()?;
+        let out = VfsPath::new(PhysicalFS::new(&self.out));
+        let options = DeployOptions {
+            delete: self.delete,
+            dry_run: self.dry_run,
+            jobs: self.jobs,
+        };
+
+        let plan = deploy(&config.bucket()?, &config, &out, &options).await?;
+        print!("{plan}");
+
+        Ok(())
+    }
+}
+
+/// Manage media stored by builds.
+#[derive(clap::Subcommand)]
+pub enum MediaCommand {
+    Gc(MediaGcCommand),
+}
+
+/// Create a new document with valid frontmatter.
+#[derive(clap::Args)]
+pub struct NewCommand {
+    /// What kind of document to create
+    #[clap(value_enum)]
+    pub kind: DocumentKind,
+
+    /// Title of the document, which its slug is made from
+    pub title: String,
+
+    /// Content sources directory
+    #[clap(short, long, default_value = ".")]
+    pub src: PathBuf,
+
+    /// IANA timezone to date the document in. Defaults to the `timezone` in
+    /// settings/*.site.yml, then the system timezone
+    #[clap(long, env = "SEAMS_TIMEZONE")]
+    pub timezone: Option<String>,
+}
+
+impl NewCommand {
+    pub fn run(self) -> anyhow::Result<()> {
+        let settings_dir = self.src.join("settings");
+        let settings = match settings_dir.exists() {
+            true => load_settings_in_dir::<SiteSettings>(
+                VfsPath::new(PhysicalFS::new(settings_dir)),
+                "site",
+            )?,
+            false => SiteSettings::default(),
+        };
+
+        let content = VfsPath::new(PhysicalFS::new(&self.src));
+        let path = match self.timezone.or(settings.timezone) {
+            Some(tz) => {
+                let tz: chrono_tz::Tz = tz
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("invalid timezone {tz:?}: {e}"))?;
+                scaffold(
+                    &content,
+                    self.kind,
+                    &self.title,
+                    chrono::Utc::now().with_timezone(&tz),
+                )?
+            }
+            None => scaffold(&content, self.kind, &self.title, chrono::Local::now())?,
+        };
+
+        println!(
+            "{}",
+            self.src
+                .join(path.as_str().trim_start_matches('/'))
+                .display()
+        );
+        Ok(())
+    }
+}
+
+/// Write JSON Schemas for document and settings files, so editors can
+/// autocomplete and validate them.
+///
+/// With --vscode, the schemas are mapped to files for the VS Code YAML
+/// extension (redhat.vscode-yaml). Settings files are matched by their
+/// naming conventions, like `*.tag.yml` and `*.navbar.yml`.
+///
+/// Posts, projects, pages and computers are only matched when their metadata
+/// is in a separate YAML file, like `index.md.yml` next to `index.md`. The
+/// YAML extension doesn't look inside markdown, so frontmatter embedded in a
+/// `.md` file gets no completion or validation. To get it for a document,
+/// move its frontmatter into a `.yml` file named after the content file.
+#[derive(clap::Args)]
+pub struct SchemaCommand {
+    /// Directory to write the schemas to
+    #[clap(short, long, default_value = "schemas")]
+    pub out: PathBuf,
+
+    /// VS Code settings file to map the schemas to their files in, like
+    /// `.vscode/settings.json`. Other settings in it are kept. Paths are
+    /// written as given, so run this from the workspace root
+    #[clap(long)]
+    pub vscode: Option<PathBuf>,
+}
+
+impl SchemaCommand {
+    pub fn run(self) -> anyhow::Result<()> {
+        let schemas = schemas();
+
+        std::fs::create_dir_all(&self.out)?;
+        for s in &schemas {
+            let path = self.out.join(s.filename());
+            std::fs::write(&path, serde_json::to_string_pretty(&s.schema)?)?;
+            info!("wrote {}", path.display());
+        }
+
+        let Some(vscode) = self.vscode else {
+            return Ok(());
+        };
+        let mut settings = match std::fs::read_to_string(&vscode) {
+            Ok(s) => serde_json::from_str::<serde_json::Map<_, _>>(&s).with_context(|| {
+                format!(
+                    "{} must be a JSON object without comments",
+                    vscode.display()
+                )
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Default::default(),
+            Err(e) => return Err(e.into()),
+        };
+        add_vscode_mappings(&mut settings, &self.out.to_string_lossy(), &schemas);
+
+        if let Some(parent) = vscode.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&vscode, serde_json::to_string_pretty(&settings)? + "\n")?;
+        info!("mapped schemas in {}", vscode.display());
+        Ok(())
+    }
+}
+
+/// Find stored media that no page uses anymore, and optionally delete it.
+///
+/// Compares storage against the media manifest written by the last build.
+#[derive(clap::Args)]
+pub struct MediaGcCommand {
+    /// Output directory of the last build
+    #[clap(default_value = "out")]
+    pub out: PathBuf,
+
+    /// Media manifest written by the last build. Defaults to
+    /// `<out>.media.json`, next to the output directory
+    #[clap(long)]
+    pub media_manifest: Option<PathBuf>,
+
+    /// Also check the bucket, not just the output directory
+    #[clap(long)]
+    pub remote: bool,
+
+    /// Delete unused media instead of just listing it
+    #[clap(long)]
+    pub delete: bool,
+
+    #[clap(flatten)]
+    pub storage: StorageArgs,
+}
+
+impl MediaGcCommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let manifest_path = match &self.media_manifest {
+            Some(p) => p.clone(),
+            None => default_manifest_path(&self.out)?,
+        };
+        let manifest = std::fs::read_to_string(&manifest_path).with_context(|| {
+            format!(
+                "Could not read the media manifest at {}, try building first",
+                manifest_path.display()
+            )
+        })?;
+        let manifest: MediaManifest = serde_json::from_str(&manifest)?;
+        let out = VfsPath::new(PhysicalFS::new(&self.out));
+        let rendered = read_rendered_text(&out)?;
+
+        let local = out.join("static")?;
+        let orphans = find_orphans(&local, "", &manifest, &rendered).await?;
+        self.collect(&local, "local", orphans).await?;
+
+        if self.remote {
+            let config = self.storage.resolve()?;
+            let bucket = config.bucket()?;
+            let orphans = find_orphans(&bucket, &config.key_prefix, &manifest, &rendered).await?;
+            self.collect(&bucket, "remote", orphans).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn collect(
+        &self,
+        store: &impl ObjectStore,
+        name: &str,
+        orphans: Vec<String>,
+    ) -> anyhow::Result<()> {
+        for key in &orphans {
+            println!("{name}: {key}");
+            if self.delete {
+                store.delete(key).await?;
+            }
+        }
+        info!(
+            count = orphans.len(),
+            deleted = self.delete,
+            "Found unused {name} media"
+        );
+        Ok(())
+    }
 }
 
-/// Upload a file to Backblaze.
+/// Upload files to S3-compatible object storage.
 ///
 /// Environment variables required: AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY
 #[derive(clap::Args)]
 pub struct UploadCommand {
     /// The files to upload
-    #[clap(required = true)]
+    #[clap(required_unless_present = "rewrite", conflicts_with = "rewrite")]
     pub src: Vec<PathBuf>,
 
-    /// The bucket to upload to
-    #[clap(short, long, default_value = "nyaabucket")]
-    pub bucket: String,
+    /// Upload every local image in this markdown file, and replace their
+    /// links with the uploaded URLs
+    #[clap(long)]
+    pub rewrite: Option<PathBuf>,
+
+    /// Maximum number of uploads at once
+    #[clap(short, long, default_value = "4")]
+    pub jobs: usize,
+
+    /// Number of times to retry a failed request, with exponential backoff
+    #[clap(long, default_value = "3")]
+    pub retries: usize,
+
+    /// Print a JSON object mapping each path to its URL and SHA-256
+    #[clap(long, conflicts_with = "markdown")]
+    pub json: bool,
+
+    /// Print a markdown image for each file, like `![](url)`
+    #[clap(long)]
+    pub markdown: bool,
+
+    #[clap(flatten)]
+    pub storage: StorageArgs,
+}
+
+/// Where to store uploaded files.
+///
+/// Flags take precedence over environment variables, which take precedence
+/// over `settings/*.storage.yml` in the site, if one is given.
+#[derive(clap::Args)]
+pub struct StorageArgs {
+    /// Content sources directory to read storage settings from
+    #[clap(long, env = "SEAMS_SITE")]
+    pub site: Option<PathBuf>,
+
+    /// The bucket to upload to [default: nyaabucket]
+    #[clap(short, long, env = "SEAMS_S3_BUCKET")]
+    pub bucket: Option<String>,
+
+    /// S3-compatible endpoint [default: https://s3.us-west-000.backblazeb2.com]
+    #[clap(long, env = "SEAMS_S3_ENDPOINT")]
+    pub endpoint: Option<String>,
+
+    /// Region to sign requests with [default: us-west-1]
+    #[clap(long, env = "SEAMS_S3_REGION")]
+    pub region: Option<String>,
+
+    /// Address the bucket as endpoint/bucket instead of bucket.endpoint.
+    /// Use --path-style=false to turn it off when settings turn it on
+    #[clap(
+        long,
+        env = "SEAMS_S3_PATH_STYLE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub path_style: Option<bool>,
+
+    /// Public URL template, with {endpoint}, {bucket} and {key} placeholders
+    /// [default: {endpoint}/{bucket}/{key}]
+    #[clap(long, env = "SEAMS_S3_PUBLIC_URL")]
+    pub public_url: Option<String>,
+
+    /// Prefix to put in front of every key
+    #[clap(long, env = "SEAMS_S3_KEY_PREFIX")]
+    pub key_prefix: Option<String>,
+}
+
+impl StorageArgs {
+    pub fn resolve(&self) -> anyhow::Result<S3Config> {
+        self.resolve_with_site(self.site.as_deref())
+    }
+
+    /// Resolve the configuration, reading settings from the given site.
+    pub fn resolve_with_site(&self, site: Option<&Path>) -> anyhow::Result<S3Config> {
+        let site = match site {
+            Some(p) => load_settings_in_dir::<StorageSettings>(
+                VfsPath::new(PhysicalFS::new(p)).join("settings")?,
+                "storage",
+            )?,
+            None => StorageSettings::default(),
+        };
+        let args = StorageSettings {
+            bucket: self.bucket.clone(),
+            endpoint: self.endpoint.clone(),
+            region: self.region.clone(),
+            path_style: self.path_style,
+            public_url: self.public_url.clone(),
+            key_prefix: self.key_prefix.clone(),
+        };
+
+        Ok(S3Config::new(&site.combine(&args)))
+    }
 }
 
 impl UploadCommand {
     pub async fn run(self) -> anyhow::Result<()> {
-        let futures = self
-            .src
-            .iter()
-            .map(|path| async {
-                let path = path.clone();
-                info!(?path, "uploading file");
+        let config = self.storage.resolve()?;
+        let bucket = config.bucket()?;
+        let retries = Retries {
+            retries: self.retries,
+            ..Default::default()
+        };
 
-                let mut data = vec![];
-                let size = BufReader::new(File::open(&path).await?)
-                    .read_to_end(&mut data)
-                    .await?;
-                data.truncate(size);
+        let rewrite = match &self.rewrite {
+            Some(post) => {
+                let raw = std::fs::read_to_string(post)?;
+                let images = find_local_images(&raw);
+                Some((post, raw, images))
+            }
+            None => None,
+        };
+        let image_path = |url: &str| {
+            let dir = self.rewrite.as_deref().and_then(Path::parent);
+            dir.unwrap_or(Path::new(""))
+                .join(url.trim().trim_start_matches("./"))
+        };
+        let paths = match &rewrite {
+            Some((_, _, images)) => images.iter().map(|i| image_path(&i.url)).unique().collect(),
+            None => self.src.clone(),
+        };
 
-                let media = Media {
-                    filename: Some(path.file_name().unwrap().to_str().unwrap().to_owned()),
-                    mimetype: mime_guess::from_path(&path).into_iter().next(),
-                    body: data,
-                };
+        let mut results = stream::iter(&paths)
+            .map(|path| {
+                let (bucket, config) = (&bucket, &config);
+                async move {
+                    info!(?path, "uploading file");
 
-                let url = upload_to_s3(&self.bucket, media).await?;
+                    let mut data = vec![];
+                    let size = BufReader::new(File::open(&path).await?)
+                        .read_to_end(&mut data)
+                        .await?;
+                    data.truncate(size);
 
-                anyhow::Ok(url)
-            })
-            .collect::<FuturesOrdered<_>>();
+                    let media = Media {
+                        filename: path.file_name().map(|n| n.to_string_lossy().into_owned()),
+                        mimetype: mime_guess::from_path(path).first(),
+                        body: data,
+                    };
 
-        let mut stream = stream::iter(self.src.iter()).zip(futures);
+                    upload_to_s3(bucket, config, media, retries).await
+                }
+            })
+            .buffered(self.jobs.max(1))
+            .zip(stream::iter(&paths));
 
-        let mut fail = false;
-        while let Some((path, result)) = stream.next().await {
+        let mut uploaded = BTreeMap::new();
+        let mut failed = 0;
+        while let Some((result, path)) = results.next().await {
             match result {
-                Ok(url) => println!("{url}"),
+                Ok(u) => {
+                    if u.existed {
+                        info!(path = %path.to_string_lossy(), "Already uploaded");
+                    }
+                    if self.markdown {
+                        println!("![]({})", u.url);
+                    } else if !self.json {
+                        println!("{}", u.url);
+                    }
+                    uploaded.insert(path.to_string_lossy().into_owned(), u);
+                }
                 Err(error) => {
                     error!(path = %path.to_string_lossy(), %error, "Error while uploading");
-                    fail = true;
+                    failed += 1;
                 }
             }
         }
 
-        if fail {
-            std::process::exit(-1);
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&uploaded)?);
+        }
+
+        if failed > 0 {
+            anyhow::bail!("{failed} of {} uploads failed", paths.len());
+        }
+
+        if let Some((post, raw, images)) = rewrite {
+            let urls = images
+                .iter()
+                .map(|i| {
+                    let path = image_path(&i.url).to_string_lossy().into_owned();
+                    (i.url.clone(), uploaded[&path].url.clone())
+                })
+                .collect();
+            let (rewritten, skipped) = rewrite_image_urls(&raw, &images, &urls);
+            for i in &skipped {
+                warn!(
+                    url = i.url,
+                    line = i.lines.start(),
+                    "Could not find where this image is written, so its link was not rewritten"
+                );
+            }
+            std::fs::write(post, rewritten)?;
+            info!(
+                path = %post.to_string_lossy(),
+                count = images.len() - skipped.len(),
+                "Rewrote image links"
+            );
         }
 
         Ok(())
@@ -114,3 +796,26 @@ pub struct WatchCommand {
     #[clap(short, long, default_value = "0")]
     pub port: u16,
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn path_style(args: &[&str]) -> Option<bool> {
+        let args = ["seams", "upload"].iter().chain(args);
+        match TopLevel::try_parse_from(args).unwrap().command {
+            Subcommand::Upload(u) => u.storage.path_style,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    pub fn path_style_is_a_switch_that_can_be_turned_off() {
+        assert_eq!(path_style(&["a.png"]), None);
+        assert_eq!(path_style(&["--path-style", "a.png"]), Some(true));
+        assert_eq!(path_style(&["--path-style=true", "a.png"]), Some(true));
+        assert_eq!(path_style(&["--path-style=false", "a.png"]), Some(false));
+    }
+}
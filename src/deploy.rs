@@ -0,0 +1,273 @@
+use std::{collections::BTreeMap, fmt::Display, io::Read};
+
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+use vfs::VfsPath;
+
+use crate::{
+    media::content_hash,
+    upload::{ObjectStore, S3Config},
+};
+
+/// Where the manifest of the currently-deployed site is stored, relative to the key prefix.
+pub const MANIFEST_KEY: &str = ".seams-manifest.json";
+
+/// The SHA-256 of every file in a deployed site.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+pub struct Manifest {
+    pub files: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    /// Hash every file in a directory.
+    pub fn from_dir(root: &VfsPath) -> anyhow::Result<Self> {
+        let mut files = BTreeMap::new();
+        for p in root.walk_dir()? {
+            let p = p?;
+            if !p.is_file()? {
+                continue;
+            }
+
+            let mut buf = vec![];
+            p.open_file()?.read_to_end(&mut buf)?;
+            let path = p.as_str().strip_prefix(root.as_str()).unwrap_or(p.as_str());
+            files.insert(path.trim_start_matches('/').to_owned(), content_hash(&buf));
+        }
+        Ok(Self { files })
+    }
+}
+
+/// What needs to happen to make the remote match the local output.
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct DeployPlan {
+    /// Files that don't exist remotely yet.
+    pub new: Vec<String>,
+
+    /// Files whose contents differ from the remote.
+    pub changed: Vec<String>,
+
+    /// Files that exist remotely, but not locally.
+    pub orphans: Vec<String>,
+
+    /// Number of files that are already up to date.
+    pub unchanged: usize,
+}
+
+impl DeployPlan {
+    pub fn new(local: &Manifest, remote: &Manifest) -> Self {
+        let mut plan = Self::default();
+        for (path, hash) in &local.files {
+            match remote.files.get(path) {
+                None => plan.new.push(path.clone()),
+                Some(h) if h != hash => plan.changed.push(path.clone()),
+                Some(_) => plan.unchanged += 1,
+            }
+        }
+        plan.orphans = remote
+            .files
+            .keys()
+            .filter(|p| !local.files.contains_key(*p))
+            .cloned()
+            .collect();
+        plan
+    }
+
+    fn uploads(&self) -> impl Iterator<Item = &String> {
+        self.new.iter().chain(&self.changed)
+    }
+}
+
+impl Display for DeployPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for p in &self.new {
+            writeln!(f, "+ {p}")?;
+        }
+        for p in &self.changed {
+            writeln!(f, "~ {p}")?;
+        }
+        for p in &self.orphans {
+            writeln!(f, "- {p}")?;
+        }
+        writeln!(
+            f,
+            "{} new, {} changed, {} orphaned, {} unchanged",
+            self.new.len(),
+            self.changed.len(),
+            self.orphans.len(),
+            self.unchanged
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DeployOptions {
+    /// Delete remote files that no longer exist locally.
+    pub delete: bool,
+
+    /// Only figure out what would happen, without changing anything.
+    pub dry_run: bool,
+
+    /// Maximum number of uploads in flight.
+    pub jobs: usize,
+}
+
+/// Make the remote match the local output directory.
+///
+/// The manifest is only written after every upload succeeds, so a failed
+/// deploy gets retried in full next time.
+#[tracing::instrument(skip_all, fields(root = root.as_str()))]
+pub async fn deploy(
+    store: &impl ObjectStore,
+    config: &S3Config,
+    root: &VfsPath,
+    options: &DeployOptions,
+) -> anyhow::Result<DeployPlan> {
+    let local = Manifest::from_dir(root)?;
+    let manifest_key = config.key(MANIFEST_KEY);
+    let remote = match store.get(&manifest_key).await? {
+        Some(m) => serde_json::from_slice(&m)?,
+        None => Manifest::default(),
+    };
+
+    let plan = DeployPlan::new(&local, &remote);
+    if options.dry_run {
+        return Ok(plan);
+    }
+
+    info!(
+        uploads = plan.new.len() + plan.changed.len(),
+        "Uploading files"
+    );
+    stream::iter(plan.uploads())
+        .map(|path| async move {
+            let mut body = vec![];
+            root.join(path)?.open_file()?.read_to_end(&mut body)?;
+            let content_type = content_type(path);
+            debug!(path, content_type, "uploading");
+            store.put(&config.key(path), &body, &content_type).await
+        })
+        .buffer_unordered(options.jobs.max(1))
+        .try_collect::<()>()
+        .await?;
+
+    let mut deployed = local;
+    if options.delete {
+        info!(count = plan.orphans.len(), "Deleting orphans");
+        for path in &plan.orphans {
+            store.delete(&config.key(path)).await?;
+        }
+    } else {
+        for path in &plan.orphans {
+            deployed
+                .files
+                .insert(path.clone(), remote.files[path].clone());
+        }
+    }
+
+    store
+        .put(
+            &manifest_key,
+            &serde_json::to_vec_pretty(&deployed)?,
+            "application/json",
+        )
+        .await?;
+
+    Ok(plan)
+}
+
+fn content_type(path: &str) -> String {
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    match mime.type_() {
+        mime::TEXT => format!("{}; charset=utf-8", mime.essence_str()),
+        _ => mime.essence_str().to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vfs::MemoryFS;
+
+    use crate::model::StorageSettings;
+
+    use super::*;
+
+    fn write(root: &VfsPath, path: &str, body: &str) {
+        let p = root.join(path).unwrap();
+        p.parent().create_dir_all().unwrap();
+        p.create_file().unwrap().write_all(body.as_bytes()).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn deploys_only_differences() {
+        let config = S3Config::new(&StorageSettings {
+            key_prefix: Some("site".into()),
+            ..Default::default()
+        });
+        let store = VfsPath::new(MemoryFS::new());
+        let out = VfsPath::new(MemoryFS::new());
+        let options = DeployOptions {
+            delete: true,
+            dry_run: false,
+            jobs: 2,
+        };
+        write(&out, "index.html", "home");
+        write(&out, "blog/index.html", "blog");
+        write(&out, "static/old.png", "old");
+        deploy(&store, &config, &out, &options).await.unwrap();
+
+        write(&out, "index.html", "new home");
+        write(&out, "projects/index.html", "projects");
+        out.join("static/old.png").unwrap().remove_file().unwrap();
+        let dry_run = DeployOptions {
+            dry_run: true,
+            ..options.clone()
+        };
+        let plan = deploy(&store, &config, &out, &dry_run).await.unwrap();
+
+        let expected = DeployPlan {
+            new: vec!["projects/index.html".into()],
+            changed: vec!["index.html".into()],
+            orphans: vec!["static/old.png".into()],
+            unchanged: 1,
+        };
+        assert_eq!(plan, expected);
+        assert!(store.join("site/static/old.png").unwrap().exists().unwrap());
+
+        let plan = deploy(&store, &config, &out, &options).await.unwrap();
+
+        assert_eq!(plan, expected);
+        assert_eq!(
+            store.get("site/index.html").await.unwrap(),
+            Some(b"new home".to_vec())
+        );
+        assert!(store
+            .get("site/projects/index.html")
+            .await
+            .unwrap()
+            .is_some());
+        assert!(!store.join("site/static/old.png").unwrap().exists().unwrap());
+        assert_eq!(
+            DeployPlan::new(
+                &Manifest::from_dir(&out).unwrap(),
+                &serde_json::from_slice(
+                    &store
+                        .get("site/.seams-manifest.json")
+                        .await
+                        .unwrap()
+                        .unwrap()
+                )
+                .unwrap()
+            )
+            .unchanged,
+            3
+        );
+    }
+
+    #[test]
+    pub fn text_gets_a_charset() {
+        assert_eq!(content_type("index.html"), "text/html; charset=utf-8");
+        assert_eq!(content_type("static/a.png"), "image/png");
+        assert_eq!(content_type("CNAME"), "application/octet-stream");
+    }
+}
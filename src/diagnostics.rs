@@ -0,0 +1,173 @@
+use std::fmt::Write;
+
+use serde::Serialize;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem with some source file, optionally pointing at where in it.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+
+    /// Source file the problem is in, relative to the content root.
+    pub path: String,
+
+    /// 1-based line in the file, if known.
+    pub line: Option<usize>,
+
+    /// 1-based column in the line, if known.
+    pub column: Option<usize>,
+
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            path: path.into(),
+            line: None,
+            column: None,
+            message: message.into(),
+        }
+    }
+
+    pub fn warning(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(path, message)
+        }
+    }
+
+    /// Point at a line and column. A line of 0 means the position is unknown.
+    pub fn at(self, line: usize, column: usize) -> Self {
+        Self {
+            line: (line > 0).then_some(line),
+            column: (line > 0 && column > 0).then_some(column),
+            ..self
+        }
+    }
+
+    /// Point at a line and a column counted in bytes, like comrak and
+    /// serde_json report them, counting the column in characters instead.
+    pub fn at_byte_column(self, source: Option<&str>, line: usize, byte_column: usize) -> Self {
+        let text = source.and_then(|s| s.lines().nth(line.checked_sub(1)?));
+        let column = match text {
+            Some(text) if byte_column > 0 => {
+                let mut end = (byte_column - 1).min(text.len());
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+                text[..end].chars().count() + 1
+            }
+            _ => byte_column,
+        };
+        self.at(line, column)
+    }
+
+    /// Point at a byte offset into the source.
+    pub fn at_offset(self, source: &str, offset: usize) -> Self {
+        let before = &source[..offset.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before
+            .rsplit('\n')
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .count()
+            + 1;
+        self.at(line, column)
+    }
+
+    /// Render for humans, like a compiler would, with the offending line of
+    /// the source and a caret under the column if they're known.
+    pub fn render(&self, source: Option<&str>) -> String {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let mut out = format!("{severity}: {}\n", self.message);
+
+        let Some(line) = self.line else {
+            writeln!(out, "  --> {}", self.path).unwrap();
+            return out;
+        };
+        match self.column {
+            Some(c) => writeln!(out, "  --> {}:{line}:{c}", self.path).unwrap(),
+            None => writeln!(out, "  --> {}:{line}", self.path).unwrap(),
+        }
+
+        let Some(text) = source.and_then(|s| s.lines().nth(line - 1)) else {
+            return out;
+        };
+        let gutter = " ".repeat(line.to_string().len());
+        writeln!(out, "{gutter} |").unwrap();
+        writeln!(out, "{line} | {text}").unwrap();
+        if let Some(c) = self.column {
+            // Keep tabs so the caret lines up with the text above it
+            let pad = text
+                .chars()
+                .take(c - 1)
+                .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+            writeln!(out, "{gutter} | {pad}^").unwrap();
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn renders_snippet_with_caret() {
+        let source = "---\ntitle: x\n---\n\n```dot\n\tdigraph {\n```\n";
+        let d = Diagnostic::error("/blog/post.md", "graphviz error").at(6, 2);
+
+        assert_eq!(
+            d.render(Some(source)),
+            "error: graphviz error\n  --> /blog/post.md:6:2\n  |\n6 | \tdigraph {\n  | \t^\n"
+        );
+        assert_eq!(
+            Diagnostic::warning("/a.md", "oops").render(None),
+            "warning: oops\n  --> /a.md\n"
+        );
+    }
+
+    #[test]
+    pub fn byte_columns_become_char_columns() {
+        let source = "---\ntitle: Café ☕ 日本\n---\n";
+        // The byte column of the `日`
+        let byte_column = source.lines().nth(1).unwrap().find('日').unwrap() + 1;
+        let d = Diagnostic::error("/a.md", "bad").at_byte_column(Some(source), 2, byte_column);
+
+        assert_eq!((d.line, d.column), (Some(2), Some(15)));
+        assert_eq!(
+            d.render(Some(source)),
+            "error: bad\n  --> /a.md:2:15\n  |\n2 | title: Café ☕ 日本\n  |               ^\n"
+        );
+        assert_eq!(
+            Diagnostic::error("/a.md", "bad")
+                .at_byte_column(None, 2, byte_column)
+                .column,
+            Some(byte_column)
+        );
+    }
+
+    #[test]
+    pub fn offsets_become_lines_and_columns() {
+        let d = Diagnostic::error("/a.yml", "bad").at_offset("a: 1\nbb: [\n", 9);
+
+        assert_eq!((d.line, d.column), (Some(2), Some(5)));
+        assert_eq!(
+            serde_json::to_string(&d).unwrap(),
+            r#"{"severity":"error","path":"/a.yml","line":2,"column":5,"message":"bad"}"#
+        );
+    }
+}
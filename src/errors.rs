@@ -23,6 +23,10 @@ impl<E> Errors<E> {
         self.errors.is_empty()
     }
 
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
     pub fn cast<E2>(self) -> Errors<E2>
     where
         E: Into<E2>,
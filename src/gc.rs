@@ -0,0 +1,97 @@
+use tracing::debug;
+use vfs::VfsPath;
+
+use crate::{media::MediaManifest, upload::ObjectStore};
+
+/// Everything in the rendered output that could mention media by its URL.
+pub fn read_rendered_text(out: &VfsPath) -> anyhow::Result<Vec<String>> {
+    let mut texts = vec![];
+    for p in out.walk_dir()? {
+        let p = p?;
+        let is_text = matches!(
+            p.extension().as_deref(),
+            Some("html" | "xml" | "css" | "js" | "json")
+        );
+        if is_text && p.is_file()? {
+            texts.push(p.read_to_string()?);
+        }
+    }
+    Ok(texts)
+}
+
+/// Find objects under the prefix that look like content-addressed media,
+/// but are neither in the manifest nor mentioned anywhere in the output.
+///
+/// Objects uploaded by hand with `seams upload` are kept as long as some
+/// page still links to them.
+#[tracing::instrument(skip_all, fields(prefix = prefix))]
+pub async fn find_orphans(
+    store: &impl ObjectStore,
+    prefix: &str,
+    manifest: &MediaManifest,
+    rendered: &[String],
+) -> anyhow::Result<Vec<String>> {
+    let mut orphans = vec![];
+
+    for key in store.list_keys(prefix).await? {
+        let path = key[prefix.len()..].trim_start_matches('/');
+        if !is_content_addressed(path) {
+            debug!(key, "skipping, not media");
+            continue;
+        }
+
+        let used = manifest.media.contains_key(path) || rendered.iter().any(|t| t.contains(path));
+        if !used {
+            orphans.push(key);
+        }
+    }
+
+    Ok(orphans)
+}
+
+/// Whether a path looks like `<sha256>/<filename>`.
+fn is_content_addressed(path: &str) -> bool {
+    let hash = path.split('/').next().unwrap_or_default();
+    hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use vfs::MemoryFS;
+
+    use crate::media::{content_hash, Media, MediaRegistry};
+
+    use super::*;
+
+    #[tokio::test]
+    pub async fn finds_unused_media() {
+        let storage = VfsPath::new(MemoryFS::new());
+        let media = MediaRegistry::new("/static".into(), storage.clone());
+        let image = |body: &[u8]| Media {
+            filename: Some("image.png".into()),
+            mimetype: Some(mime::IMAGE_PNG),
+            body: body.to_vec(),
+        };
+        let used_url = media.upload_media(image(b"used")).unwrap();
+        let manifest = media
+            .manifest([("/blog/post.md", format!("<img src={used_url:?}>").as_str())])
+            .unwrap();
+
+        let linked = format!("{}/cat.png", content_hash(b"linked"));
+        let orphan = format!("{}/old.png", content_hash(b"old"));
+        for key in [&linked, &orphan, &"robots.txt".to_string()] {
+            storage.put(key, b"", "image/png").await.unwrap();
+        }
+        let rendered = vec![format!(r#"<a href="https://cdn.example.com/{linked}">"#)];
+
+        let orphans = find_orphans(&storage, "", &manifest, &rendered)
+            .await
+            .unwrap();
+
+        assert_eq!(orphans, vec![orphan]);
+        assert_eq!(
+            manifest.media.values().next().unwrap().referenced_by,
+            vec!["/blog/post.md"]
+        );
+    }
+}
@@ -1,23 +1,33 @@
 use std::borrow::Cow;
 
 use futures::{stream::FuturesUnordered, StreamExt};
-use gray_matter::{engine::YAML, Matter, Pod};
 use serde::de::DeserializeOwned;
 use tracing::trace;
 use vfs::{VfsError, VfsPath};
 
 use crate::{
     errors::Errors,
-    load::util::split_extension,
+    load::{
+        strict::{deserialize_meta, UnknownField},
+        util::{parse_frontmatter, split_extension, FrontmatterFormat},
+    },
     media::MediaRegistry,
+    model::RouteCollision,
     transform::{
+        asciidoc::transform_asciidoc,
         common::TransformContext,
+        jupyter::{transform_notebook, JupyterError},
+        links::LinkTargets,
         markdown::{transform_markdown, MarkdownError},
+        org::transform_org,
+        statistics::DocumentStatistics,
     },
 };
 
 use super::site_data::SiteDataUserError;
 
+pub use crate::transform::common::TransformedContent;
+
 /// A document that has metadata and a piece of content associated with it.
 #[derive(Clone, Debug)]
 pub struct Document<M> {
@@ -42,6 +52,9 @@ pub struct Content {
 
     /// Raw data of the content.
     pub raw: String,
+
+    /// How many lines of the file come before the content, like frontmatter.
+    pub line_offset: usize,
 }
 
 /// Where the content is relative to the meta file.
@@ -54,22 +67,35 @@ pub enum ContentSource {
     FileRef(VfsPath),
 }
 
-/// Content that has been transformed into HTML.
-#[derive(Clone)]
-pub struct TransformedContent {
-    /// The raw HTML.
-    pub html: String,
-}
-
 /// A ContentType supported by this system.
 #[derive(Clone, Copy, Debug)]
 pub enum ContentType {
     Plaintext,
     Markdown,
     Html,
-    // Jupyter,
+    Jupyter,
+    Org,
+    AsciiDoc,
 }
 
+impl ContentType {
+    /// The type of content files with the given extension, if it's supported.
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            "txt" => Some(ContentType::Plaintext),
+            "md" | "markdown" => Some(ContentType::Markdown),
+            "html" => Some(ContentType::Html),
+            "ipynb" => Some(ContentType::Jupyter),
+            "org" => Some(ContentType::Org),
+            "adoc" | "asciidoc" => Some(ContentType::AsciiDoc),
+            _ => None,
+        }
+    }
+}
+
+/// Extensions of metadata files that refer to a separate content file.
+const SIDECAR_EXTENSIONS: [&str; 3] = ["yml", "yaml", "toml"];
+
 /// Top-level errors.
 #[derive(thiserror::Error, Debug)]
 pub enum LoadError {
@@ -84,6 +110,9 @@ pub enum LoadError {
 
     #[error("Error loading from /settings/*.{1}: {0}")]
     SettingsError(anyhow::Error, String),
+
+    #[error("Route collision: {0}")]
+    RouteCollision(#[from] RouteCollision),
 }
 
 /// Errors regarding the document load phase.
@@ -98,14 +127,17 @@ pub enum DocumentLoadError {
     #[error("Filesystem error: {0}")]
     FsError(#[from] VfsError),
 
-    #[error("Failed to parse object: {0}")]
-    ParseObjectError(#[from] serde_json::Error),
+    #[error("Failed to parse JSON: {0:#}")]
+    ParseJsonError(#[from] serde_path_to_error::Error<serde_json::Error>),
 
     #[error("Failed to parse YAML: {0:#}")]
     ParseYamlError(#[from] serde_path_to_error::Error<serde_yaml::Error>),
 
-    #[error("Failed to parse TOML: {0}")]
-    ParseTomlError(#[from] toml::de::Error),
+    #[error("Failed to parse TOML: {0:#}")]
+    ParseTomlError(#[from] serde_path_to_error::Error<toml::de::Error>),
+
+    #[error("{}", .0.iter().map(|f| f.to_string()).collect::<Vec<_>>().join("; "))]
+    UnknownFields(Vec<UnknownField>),
 }
 
 impl DocumentLoadError {
@@ -129,8 +161,9 @@ pub enum ContentLoadError {
 /// Recursively load all documents in directory.
 fn load_docs_in_dir<M: DeserializeOwned>(
     path: VfsPath,
+    strict: bool,
 ) -> Result<impl Iterator<Item = Result<Document<M>, (VfsPath, DocumentLoadError)>>, VfsError> {
-    Ok(path.walk_dir()?.filter_map(|p| {
+    Ok(path.walk_dir()?.filter_map(move |p| {
         let Ok(p) = p else { return None };
 
         let span = tracing::debug_span!("load_document", path = p.as_str());
@@ -139,8 +172,17 @@ fn load_docs_in_dir<M: DeserializeOwned>(
         if !p.is_file().unwrap() {
             return None;
         };
+        if has_sidecar(&p) {
+            trace!("skipping content file of another document");
+            return None;
+        }
 
-        match Document::load(p.clone()) {
+        let doc = if strict {
+            Document::load_strict(p.clone())
+        } else {
+            Document::load(p.clone())
+        };
+        match doc {
             Ok(d) => Some(Ok(d)),
             Err(e) if e.is_non_document() => {
                 trace!("skipping non-document file: {e}");
@@ -151,26 +193,48 @@ fn load_docs_in_dir<M: DeserializeOwned>(
     }))
 }
 
-/// Recursively load all the documents in a directory and their contents.
-pub async fn fully_load_docdir<M: DeserializeOwned>(
-    media: &MediaRegistry,
+/// Whether a metadata file refers to this one as its content, like
+/// `foo.md.yml` does to `foo.md`.
+fn has_sidecar(path: &VfsPath) -> bool {
+    SIDECAR_EXTENSIONS.iter().any(|ext| {
+        path.parent()
+            .join(format!("{}.{ext}", path.filename()))
+            .and_then(|p| p.exists())
+            .unwrap_or(false)
+    })
+}
+
+/// Recursively load all the documents in a directory, without their contents.
+///
+/// If `strict`, unknown metadata fields are errors.
+pub fn load_docdir<M: DeserializeOwned>(
     path: VfsPath,
-) -> Result<Vec<Result<FullyLoadedDocument<M>, SiteDataUserError>>, VfsError> {
-    let docs = load_docs_in_dir(path)?;
+    strict: bool,
+) -> Result<Vec<Result<Document<M>, SiteDataUserError>>, VfsError> {
+    let mut docs = vec![];
+    for d in load_docs_in_dir(path, strict)? {
+        docs.push(match d {
+            Ok(d) => Ok(d),
+            Err((path, e)) => Err(SiteDataUserError {
+                path,
+                error: e.into(),
+            }),
+        });
+    }
+    Ok(docs)
+}
 
+/// Load and transform the contents of all the given documents.
+pub async fn fully_load_docs<M: DeserializeOwned>(
+    media: &MediaRegistry,
+    links: &LinkTargets,
+    docs: Vec<Document<M>>,
+) -> Vec<Result<FullyLoadedDocument<M>, SiteDataUserError>> {
     let futures = docs
+        .into_iter()
         .map(|d| async move {
-            let d = match d {
-                Ok(d) => d,
-                Err((p, e)) => {
-                    return Err(SiteDataUserError {
-                        path: p,
-                        error: e.into(),
-                    })
-                }
-            };
             let content_path = d.content.path();
-            match d.fully_load_content(media).await {
+            match d.fully_load_content(media, links).await {
                 Ok(fld) => Ok(fld),
                 Err(e) => Err(SiteDataUserError {
                     path: content_path,
@@ -180,7 +244,7 @@ pub async fn fully_load_docdir<M: DeserializeOwned>(
         })
         .collect::<FuturesUnordered<_>>();
 
-    Ok(futures.collect::<Vec<_>>().await)
+    futures.collect::<Vec<_>>().await
 }
 
 #[derive(Clone)]
@@ -198,12 +262,19 @@ impl<M> FullyLoadedDocument<M> {
     pub fn html(&self) -> &str {
         &self.transformed.html
     }
+
+    pub fn stats(&self) -> &DocumentStatistics {
+        &self.transformed.stats
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum ContentTransformError {
     #[error("error: {0}")]
     Markdown(#[from] Errors<MarkdownError>),
+
+    #[error("notebook error: {0}")]
+    Jupyter(#[from] JupyterError),
 }
 
 impl<M> Document<M>
@@ -214,6 +285,16 @@ where
     ///
     /// Does not load content.
     pub fn load(path: VfsPath) -> Result<Self, DocumentLoadError> {
+        Self::load_impl(path, false)
+    }
+
+    /// Like [Document::load], but metadata fields that don't exist are errors
+    /// instead of being silently ignored.
+    pub fn load_strict(path: VfsPath) -> Result<Self, DocumentLoadError> {
+        Self::load_impl(path, true)
+    }
+
+    fn load_impl(path: VfsPath, strict: bool) -> Result<Self, DocumentLoadError> {
         use DocumentLoadError::*;
         let (noext, ext) = split_extension(path.as_str());
         let noext = path.root().join(noext)?;
@@ -222,34 +303,49 @@ where
 
         match ext.as_ref() {
             "md" | "markdown" => {
-                let entity = Matter::<YAML>::new().parse(&file_content);
-                let Some(frontmatter): Option<Pod> = entity.data else {
+                let Some(fm) = parse_frontmatter(&file_content) else {
                     return Err(MarkdownHasNoFrontmatter);
                 };
-                let meta: M = frontmatter.deserialize()?;
+                let meta = match fm.format {
+                    FrontmatterFormat::Yaml => {
+                        deserialize_meta(serde_yaml::Deserializer::from_str(&fm.data), strict)?
+                    }
+                    FrontmatterFormat::Toml => {
+                        deserialize_meta(toml::Deserializer::new(&fm.data), strict)?
+                    }
+                    FrontmatterFormat::Json => {
+                        deserialize_meta(&mut serde_json::Deserializer::from_str(&fm.data), strict)?
+                    }
+                }
+                .map_err(UnknownFields)?;
+                let body = fm.body.trim_start_matches('\n');
+                let line_offset = file_content[..file_content.len() - body.len()]
+                    .matches('\n')
+                    .count();
 
                 Ok(Self {
                     path: path.clone(),
                     meta,
                     content: ContentSource::Embedded(Content {
                         content_type: ContentType::Markdown,
-                        raw: entity.content,
+                        raw: body.to_owned(),
                         path,
+                        line_offset,
                     }),
                 })
             }
 
             "yml" | "yaml" => Ok(Self {
                 path,
-                meta: serde_path_to_error::deserialize(serde_yaml::Deserializer::from_str(
-                    &file_content,
-                ))?,
+                meta: deserialize_meta(serde_yaml::Deserializer::from_str(&file_content), strict)?
+                    .map_err(UnknownFields)?,
                 content: ContentSource::FileRef(noext),
             }),
 
             "toml" => Ok(Self {
                 path,
-                meta: toml::from_str(&file_content)?,
+                meta: deserialize_meta(toml::Deserializer::new(&file_content), strict)?
+                    .map_err(UnknownFields)?,
                 content: ContentSource::FileRef(noext),
             }),
 
@@ -261,9 +357,10 @@ where
     pub async fn fully_load_content(
         self,
         media: &MediaRegistry,
+        links: &LinkTargets,
     ) -> Result<FullyLoadedDocument<M>, LoadError> {
         let content = self.content.load()?.into_owned();
-        let transformed = content.transform(media).await?;
+        let transformed = content.transform(media, links).await?;
 
         Ok(FullyLoadedDocument {
             document: self,
@@ -289,18 +386,16 @@ impl ContentSource {
                 let Some(ext) = path.extension() else {
                     return Err(UnknownExtension("".into()));
                 };
-                let raw = path.read_to_string()?;
-
-                let content_type = match ext.as_str() {
-                    "html" => ContentType::Html,
-                    "txt" => ContentType::Plaintext,
-                    e => return Err(UnknownExtension(e.into())),
+                let Some(content_type) = ContentType::from_extension(&ext) else {
+                    return Err(UnknownExtension(ext));
                 };
+                let raw = path.read_to_string()?;
 
                 Ok(Cow::Owned(Content {
                     content_type,
                     raw,
                     path: path.clone(),
+                    line_offset: 0,
                 }))
             }
         }
@@ -323,20 +418,51 @@ impl Content {
     pub async fn transform(
         &self,
         media: &MediaRegistry,
+        links: &LinkTargets,
     ) -> Result<TransformedContent, ContentTransformError> {
         match self.content_type {
             ContentType::Plaintext => Ok(TransformedContent {
                 html: format!("<pre>{}</pre>", html_escape::encode_text(&self.raw)),
+                stats: DocumentStatistics::from_text(&self.raw),
             }),
-            ContentType::Markdown => Ok(TransformedContent {
-                html: transform_markdown(
-                    &TransformContext::new(self.content_root(), media),
-                    &self.raw,
-                )
-                .await?,
-            }),
+            ContentType::Markdown => Ok(transform_markdown(
+                &TransformContext::new(self.content_root(), media, links),
+                &self.raw,
+            )
+            .await
+            .map_err(|es| {
+                es.into_iter()
+                    .map(|e| e.offset_lines(self.line_offset))
+                    .collect::<Errors<_>>()
+            })?),
+            ContentType::Org => Ok(transform_org(
+                &TransformContext::new(self.content_root(), media, links),
+                &self.raw,
+            )
+            .await
+            .map_err(|es| {
+                es.into_iter()
+                    .map(|e| e.offset_lines(self.line_offset))
+                    .collect::<Errors<_>>()
+            })?),
+            ContentType::AsciiDoc => Ok(transform_asciidoc(
+                &TransformContext::new(self.content_root(), media, links),
+                &self.raw,
+            )
+            .await
+            .map_err(|es| {
+                es.into_iter()
+                    .map(|e| e.offset_lines(self.line_offset))
+                    .collect::<Errors<_>>()
+            })?),
+            ContentType::Jupyter => Ok(transform_notebook(
+                &TransformContext::new(self.content_root(), media, links),
+                &self.raw,
+            )
+            .await?),
             ContentType::Html => Ok(TransformedContent {
                 html: self.raw.clone(),
+                stats: DocumentStatistics::from_html(&self.raw),
             }),
         }
     }
@@ -348,7 +474,7 @@ mod test {
     use assert_matches::*;
     use rstest::*;
     use serde::Deserialize;
-    use vfs::{PhysicalFS, VfsPath};
+    use vfs::{MemoryFS, PhysicalFS, VfsPath};
 
     #[derive(Deserialize)]
     struct Meta {
@@ -378,6 +504,27 @@ mod test {
         Ok(())
     }
 
+    #[rstest]
+    #[case("---\nfield: value\n---\n\n\nbody\n")]
+    #[case("+++\nfield = \"value\"\n+++\n\n\nbody\n")]
+    #[case(";;;\n{\"field\": \"value\"}\n;;;\n\n\nbody\n")]
+    #[case("{\n  \"field\": \"value\"\n}\n\nbody\n")]
+    fn load_any_frontmatter_format(#[case] source: &str) -> anyhow::Result<()> {
+        let path = VfsPath::new(MemoryFS::new()).join("doc.md")?;
+        path.create_file()?.write_all(source.as_bytes())?;
+
+        let doc = Document::<Meta>::load(path)?;
+        let ContentSource::Embedded(content) = doc.content else {
+            panic!("markdown should be embedded");
+        };
+
+        assert_eq!(doc.meta.field, "value");
+        assert_eq!(content.raw, "body\n");
+        assert_eq!(source.lines().nth(content.line_offset), Some("body"));
+
+        Ok(())
+    }
+
     #[rstest]
     fn load_separated_yml(test_data: VfsPath) -> anyhow::Result<()> {
         let doc = Document::<Meta>::load(test_data.join("my_yaml_doc.txt.yml")?)?;
@@ -394,6 +541,35 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn load_separated_markdown() -> anyhow::Result<()> {
+        let dir = VfsPath::new(MemoryFS::new());
+        dir.join("big_post.md.yml")?
+            .create_file()?
+            .write_all(b"field: separated")?;
+        dir.join("big_post.md")?
+            .create_file()?
+            .write_all(b"# Hello\n\nThe body lives *elsewhere*.")?;
+
+        let docs = load_docdir::<Meta>(dir, false)?
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow::anyhow!("{e}"))?;
+        assert_eq!(docs.len(), 1, "the .md should not be its own document");
+
+        let content = docs[0].content.load()?;
+        assert_matches!(content.content_type, ContentType::Markdown);
+        let media = MediaRegistry::new("/static".into(), VfsPath::new(MemoryFS::new()));
+        let transformed = content.transform(&media, &LinkTargets::default()).await?;
+        assert!(
+            transformed.html.contains("<em>elsewhere</em>"),
+            "html = {}",
+            transformed.html
+        );
+
+        Ok(())
+    }
+
     #[rstest]
     fn fails_to_load_yaml_without_associated_content(test_data: VfsPath) -> anyhow::Result<()> {
         let doc = Document::<Meta>::load(
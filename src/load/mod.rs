@@ -1,4 +1,5 @@
 pub mod document;
 pub mod settings;
 pub mod site_data;
-mod util;
+pub mod strict;
+pub mod util;
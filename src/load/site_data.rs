@@ -7,17 +7,23 @@ use tracing::warn;
 use vfs::{VfsError, VfsPath};
 
 use crate::{
+    diagnostics::Diagnostic,
     errors::Errors,
     load::{
-        document::{fully_load_docdir, FullyLoadedDocument, LoadError},
+        document::{
+            fully_load_docs, load_docdir, ContentTransformError, Document, DocumentLoadError,
+            FullyLoadedDocument, LoadError,
+        },
         settings::load_settings_in_dir,
     },
     media::MediaRegistry,
     model::{
         computers::Computer,
         metadata::{ArbitraryPage, Post, Project},
-        Button88x31, NavbarItem, NewsItem, SiteData, TagSettingsSheet, Webring,
+        Button88x31, NavbarItem, NewsItem, RouteTable, SiteData, SiteSettings, StatsSettings,
+        TagSettingsSheet, Webring,
     },
+    transform::links::{LinkKind, LinkTarget, LinkTargets},
 };
 
 pub struct SiteDataLoader<'a> {
@@ -41,6 +47,78 @@ pub struct SiteDataUserError {
     pub error: LoadError,
 }
 
+impl SiteDataUserError {
+    /// Describe the error, pointing at where in the file it happened if that
+    /// is known. Markdown can have many errors at once.
+    pub fn diagnostics(&self, source: Option<&str>) -> Vec<Diagnostic> {
+        let path = self.path.as_str();
+        match &self.error {
+            LoadError::ContentTransform(ContentTransformError::Markdown(es)) => es
+                .into_iter()
+                .map(|e| {
+                    let start = e.pos().start;
+                    Diagnostic::error(path, e.kind().to_string()).at_byte_column(
+                        source,
+                        start.line,
+                        start.column,
+                    )
+                })
+                .collect(),
+            LoadError::DocumentLoad(DocumentLoadError::ParseYamlError(e)) => {
+                let d = Diagnostic::error(path, e.to_string());
+                vec![match e.inner().location() {
+                    Some(l) => d.at(l.line(), l.column()),
+                    None => d,
+                }]
+            }
+            LoadError::DocumentLoad(DocumentLoadError::ParseTomlError(e)) => {
+                let message = e.inner().message();
+                let d = Diagnostic::error(
+                    path,
+                    match e.path().to_string().as_str() {
+                        "." => message.to_owned(),
+                        p => format!("{p}: {message}"),
+                    },
+                );
+                vec![match (e.inner().span(), source) {
+                    (Some(span), Some(source)) => d.at_offset(source, span.start),
+                    _ => d,
+                }]
+            }
+            LoadError::DocumentLoad(DocumentLoadError::ParseJsonError(e)) => {
+                vec![Diagnostic::error(path, e.to_string()).at_byte_column(
+                    source,
+                    e.inner().line(),
+                    e.inner().column(),
+                )]
+            }
+            LoadError::DocumentLoad(DocumentLoadError::UnknownFields(fields)) => fields
+                .iter()
+                .map(|f| {
+                    let d = Diagnostic::error(path, f.to_string());
+                    match source.and_then(|s| find_key(s, &f.path)) {
+                        Some((line, column)) => d.at(line, column),
+                        None => d,
+                    }
+                })
+                .collect(),
+            e => vec![Diagnostic::error(path, format!("{e:#}"))],
+        }
+    }
+}
+
+/// Guess where a key is defined, as a line and column. Only the last segment
+/// of the path is looked for, so this is the first key with that name.
+fn find_key(source: &str, path: &str) -> Option<(usize, usize)> {
+    let key = path.rsplit('.').next()?;
+    source.lines().enumerate().find_map(|(i, line)| {
+        let trimmed = line.trim_start();
+        let rest = trimmed.strip_prefix(key)?.trim_start();
+        (rest.starts_with(':') || rest.starts_with('='))
+            .then(|| (i + 1, line.len() - trimmed.len() + 1))
+    })
+}
+
 impl Display for SiteDataUserError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "In file {}:\n  {:#}", self.path.as_str(), self.error)
@@ -66,16 +144,33 @@ impl<'a> SiteDataLoader<'a> {
             }
         }
 
+        let strict = self
+            .load_settings::<SiteSettings>("site")
+            .await?
+            .and_then(|s| s.strict_frontmatter)
+            .unwrap_or_default();
+
+        // Metadata of every document must be known before transforming
+        // content, so that internal links can be resolved.
+        let posts = self.load_documents::<Post>("blog", strict).await?;
+        let projects = self.load_documents::<Project>("projects", strict).await?;
+        let pages = self
+            .load_documents::<ArbitraryPage>("pages", strict)
+            .await?;
+        let computers = self.load_documents::<Computer>("computers", strict).await?;
+        let links = build_link_targets(&posts, &projects, &pages, &computers);
+
         parallel_run_and_unwrap! {
-            let posts = self.load_docdir::<Post>("blog");
-            let projects = self.load_docdir::<Project>("projects");
-            let pages = self.load_docdir::<ArbitraryPage>("pages");
-            let computers = self.load_docdir::<Computer>("computers");
+            let posts = self.load_contents(posts, &links);
+            let projects = self.load_contents(projects, &links);
+            let pages = self.load_contents(pages, &links);
+            let computers = self.load_contents(computers, &links);
             let tags = self.load_settings::<TagSettingsSheet>("tag");
             let news = self.load_settings::<Vec<NewsItem>>("news");
             let buttons = self.load_settings::<Vec<Button88x31>>("88x31");
             let webrings = self.load_settings::<Vec<Webring>>("webring");
             let navbar = self.load_settings::<Vec<NavbarItem>>("navbar");
+            let stats = self.load_settings::<StatsSettings>("stats");
         };
 
         let extra_head = match load_extra_head(&self.path) {
@@ -99,7 +194,7 @@ impl<'a> SiteDataLoader<'a> {
         }
         let tags = tags.materialize(additional_tags);
 
-        Ok(SiteData {
+        let mut sd = SiteData {
             posts,
             projects,
             tags,
@@ -109,18 +204,49 @@ impl<'a> SiteDataLoader<'a> {
             navbar,
             buttons,
             webrings,
+            stats,
             extra_head,
-        })
+            routes: Default::default(),
+            mirrored_images: Default::default(),
+        };
+
+        // Catch documents overwriting each other before anything gets rendered
+        let (routes, collisions) = RouteTable::new(&sd);
+        collisions
+            .into_iter()
+            .map(|c| SiteDataUserError {
+                path: c.path.clone(),
+                error: c.into(),
+            })
+            .collect::<Errors<_>>()
+            .into_result()?;
+        sd.routes = routes;
+
+        Ok(sd)
     }
 
-    async fn load_docdir<M: DeserializeOwned>(
+    async fn load_documents<M: DeserializeOwned>(
         &self,
         dir: &str,
-    ) -> Result<Option<Vec<FullyLoadedDocument<M>>>, SiteDataLoadError> {
+        strict: bool,
+    ) -> Result<Vec<Document<M>>, SiteDataLoadError> {
         let path = self.path.join(dir)?;
+        let (docs, errs): (Vec<Document<M>>, Vec<_>) = load_docdir::<M>(path, strict)?
+            .into_iter()
+            .partition_result();
+
+        self.errors.lock().await.extend(errs);
+        Ok(docs)
+    }
+
+    async fn load_contents<M: DeserializeOwned>(
+        &self,
+        docs: Vec<Document<M>>,
+        links: &LinkTargets,
+    ) -> Result<Option<Vec<FullyLoadedDocument<M>>>, SiteDataLoadError> {
         let (rs, errs): (Vec<FullyLoadedDocument<M>>, Vec<_>) =
-            fully_load_docdir::<M>(self.media, path)
-                .await?
+            fully_load_docs::<M>(self.media, links, docs)
+                .await
                 .into_iter()
                 .partition_result();
 
@@ -146,6 +272,46 @@ impl<'a> SiteDataLoader<'a> {
     }
 }
 
+fn build_link_targets(
+    posts: &[Document<Post>],
+    projects: &[Document<Project>],
+    pages: &[Document<ArbitraryPage>],
+    computers: &[Document<Computer>],
+) -> LinkTargets {
+    let mut links = LinkTargets::default();
+
+    for p in posts {
+        let target = LinkTarget {
+            href: p.meta.href(),
+            title: p.meta.title.clone(),
+        };
+        links.insert(LinkKind::Post, &p.meta.slug.name, target);
+    }
+    for p in projects {
+        let target = LinkTarget {
+            href: p.meta.href(),
+            title: p.meta.title.clone(),
+        };
+        links.insert(LinkKind::Project, &p.meta.slug, target);
+    }
+    for p in pages {
+        let target = LinkTarget {
+            href: p.meta.slug.clone(),
+            title: p.meta.title.clone(),
+        };
+        links.insert(LinkKind::Page, p.meta.slug.trim_matches('/'), target);
+    }
+    for c in computers {
+        let target = LinkTarget {
+            href: c.meta.href(),
+            title: c.meta.name.clone(),
+        };
+        links.insert(LinkKind::Computer, &c.meta.slug, target);
+    }
+
+    links
+}
+
 fn load_extra_head(path: &VfsPath) -> anyhow::Result<String> {
     let mut buf = String::new();
     path.join("settings/head.html")?
@@ -156,13 +322,25 @@ fn load_extra_head(path: &VfsPath) -> anyhow::Result<String> {
 
 #[cfg(test)]
 mod test {
-    use std::fs::{create_dir_all, remove_dir_all};
+    use std::{
+        collections::HashMap,
+        fs::{create_dir_all, remove_dir_all},
+    };
 
-    use vfs::{PhysicalFS, VfsPath};
+    use vfs::{MemoryFS, PhysicalFS, VfsPath};
 
     use crate::media::MediaRegistry;
 
-    use super::SiteData;
+    use comrak::nodes::{LineColumn, Sourcepos};
+
+    use crate::{
+        errors::Errors,
+        load::document::{ContentSource, Document, LoadError},
+        model::metadata::ArbitraryPage,
+        transform::{gallery::GalleryError, markdown::MarkdownError},
+    };
+
+    use super::{SiteData, SiteDataLoadError, SiteDataUserError};
 
     #[tokio::test]
     pub async fn loads_example_content_dir_correctly() {
@@ -175,4 +353,206 @@ mod test {
 
         let _sd = SiteData::load(content_path, &media).await.unwrap();
     }
+
+    #[tokio::test]
+    pub async fn example_content_dir_has_backlinks() {
+        let content_path = VfsPath::new(PhysicalFS::new("test_data/astrid_dot_tech_example"));
+        let out = VfsPath::new(MemoryFS::new());
+        let media = MediaRegistry::new("https://test".into(), out.join("static").unwrap());
+
+        let sd = SiteData::load(content_path, &media).await.unwrap();
+        let index = sd.build_index();
+
+        let backlinks = index.backlinks("/projects/astrid-tech");
+        assert!(
+            backlinks
+                .iter()
+                .any(|b| b.href == "/2020/07/26/0/gatsby-backend"),
+            "backlinks = {backlinks:?}"
+        );
+    }
+
+    #[tokio::test]
+    pub async fn backlinks_are_sorted_by_href() {
+        let content_path = VfsPath::new(PhysicalFS::new("test_data/astrid_dot_tech_example"));
+        let out = VfsPath::new(MemoryFS::new());
+        let media = MediaRegistry::new("https://test".into(), out.join("static").unwrap());
+        let mut sd = SiteData::load(content_path, &media).await.unwrap();
+
+        let hrefs = |sd: &SiteData| {
+            sd.build_index()
+                .backlinks
+                .into_iter()
+                .map(|(target, links)| {
+                    let links = links.into_iter().map(|b| b.href).collect::<Vec<_>>();
+                    (target.to_owned(), links)
+                })
+                .collect::<HashMap<_, _>>()
+        };
+
+        let before = hrefs(&sd);
+        sd.posts.reverse();
+        sd.projects.reverse();
+        sd.pages.reverse();
+        let after = hrefs(&sd);
+
+        assert!(before.values().any(|links| links.len() > 1));
+        for (target, links) in &after {
+            assert!(
+                links.windows(2).all(|w| w[0] <= w[1]),
+                "backlinks to {target} = {links:?}"
+            );
+        }
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    pub fn errors_point_at_real_file_lines() {
+        let content = VfsPath::new(MemoryFS::new());
+        let md = "---\ntitle: About\ntags: []\nslug: /about\n---\n\nHello\n\n```gallery\n./nope/*.jpg\n```\n";
+        let path = content.join("about.md").unwrap();
+        path.create_file()
+            .unwrap()
+            .write_all(md.as_bytes())
+            .unwrap();
+
+        let doc = Document::<ArbitraryPage>::load(path.clone()).unwrap();
+        let ContentSource::Embedded(c) = doc.content else {
+            panic!("markdown should be embedded");
+        };
+        // comrak only sees the body, where the fence is on line 3
+        let pos = Sourcepos {
+            start: LineColumn { line: 3, column: 1 },
+            end: LineColumn { line: 5, column: 3 },
+        };
+        let kind = GalleryError::NoMatches("./nope/*.jpg".into()).into();
+        let errors = Errors::from_iter([MarkdownError::new(pos, kind).offset_lines(c.line_offset)]);
+        let error = SiteDataUserError {
+            path,
+            error: LoadError::ContentTransform(errors.into()),
+        };
+
+        let diagnostics = error.diagnostics(Some(md));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].path, "/about.md");
+        assert_eq!(
+            (diagnostics[0].line, diagnostics[0].column),
+            (Some(9), Some(1))
+        );
+        let rendered = diagnostics[0].render(Some(md));
+        assert!(
+            rendered.contains("9 | ```gallery\n  | ^"),
+            "rendered = {rendered}"
+        );
+    }
+
+    #[test]
+    pub fn yaml_errors_point_at_real_file_lines() {
+        let content = VfsPath::new(MemoryFS::new());
+        let yml = "title: About\ntags: 5\nslug: /about\n";
+        let path = content.join("about.html.yml").unwrap();
+        path.create_file()
+            .unwrap()
+            .write_all(yml.as_bytes())
+            .unwrap();
+
+        let error = SiteDataUserError {
+            error: Document::<ArbitraryPage>::load(path.clone())
+                .err()
+                .unwrap()
+                .into(),
+            path,
+        };
+
+        let diagnostics = error.diagnostics(Some(yml));
+        assert_eq!(
+            (diagnostics[0].line, diagnostics[0].column),
+            (Some(2), Some(7))
+        );
+    }
+
+    #[test]
+    pub fn frontmatter_errors_point_at_real_file_lines() {
+        let content = VfsPath::new(MemoryFS::new());
+        let cases = [
+            (
+                "yaml.md",
+                "---\ntitle: About\nslug: /about\ntags: 5\n---\n\nHi",
+                "tags: invalid type",
+                (4, 7),
+            ),
+            (
+                "toml.md",
+                "+++\ntitle = \"About\"\nslug = \"/about\"\ntags = 5\n+++\n\nHi",
+                "tags: invalid type",
+                (4, 8),
+            ),
+            (
+                "json.md",
+                ";;;\n{\n  \"title\": \"About\",\n  \"slug\": \"/about\",\n  \"tags\": 5\n}\n;;;\n\nHi",
+                "tags: invalid type",
+                (5, 11),
+            ),
+            (
+                "bare.md",
+                "{\"title\": \"About\", \"slug\": \"/about\",\n \"tags\": [\"a\" \"b\"]}\n\nHi",
+                "expected `,` or `]`",
+                (2, 15),
+            ),
+        ];
+
+        for (name, source, message, (line, column)) in cases {
+            let path = content.join(name).unwrap();
+            path.create_file()
+                .unwrap()
+                .write_all(source.as_bytes())
+                .unwrap();
+            let error = SiteDataUserError {
+                error: Document::<ArbitraryPage>::load(path.clone())
+                    .err()
+                    .unwrap()
+                    .into(),
+                path,
+            };
+
+            let d = &error.diagnostics(Some(source))[0];
+            assert!(d.message.contains(message), "{name}: {d:?}");
+            assert_eq!((d.line, d.column), (Some(line), Some(column)), "{name}");
+        }
+    }
+
+    #[tokio::test]
+    pub async fn strict_mode_reports_unknown_frontmatter_fields() {
+        let content = VfsPath::new(MemoryFS::new());
+        let write = |path: &str, body: &str| {
+            let p = content.join(path).unwrap();
+            p.parent().create_dir_all().unwrap();
+            p.create_file().unwrap().write_all(body.as_bytes()).unwrap();
+        };
+        let md = "---\ntitle: About\ntags: []\nslug: /about\ntag: [cats]\n---\n";
+        write("pages/about.md", md);
+        for dir in ["blog", "projects", "computers", "settings"] {
+            content.join(dir).unwrap().create_dir_all().unwrap();
+        }
+        let media = MediaRegistry::new("/static".into(), VfsPath::new(MemoryFS::new()));
+
+        SiteData::load(content.clone(), &media).await.unwrap();
+
+        write("settings/site.site.yml", "strict_frontmatter: true\n");
+        let Err(SiteDataLoadError::UserError(errors)) = SiteData::load(content, &media).await
+        else {
+            panic!("expected unknown field errors");
+        };
+        let errors = errors.into_iter().collect::<Vec<_>>();
+        assert_eq!(errors.len(), 1);
+        let diagnostics = errors[0].diagnostics(Some(md));
+        assert_eq!(
+            diagnostics[0].message,
+            "unknown field `tag`, did you mean `tags`?"
+        );
+        assert_eq!(
+            (diagnostics[0].line, diagnostics[0].column),
+            (Some(5), Some(1))
+        );
+    }
 }
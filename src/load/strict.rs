@@ -0,0 +1,167 @@
+use std::fmt::Display;
+
+use serde::{
+    de::{self, DeserializeOwned, Visitor},
+    forward_to_deserialize_any, Deserializer,
+};
+
+/// A field in some metadata that nothing reads, probably because of a typo.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownField {
+    /// Path to the field, like `date.creatd`.
+    pub path: String,
+
+    /// The known field with the most similar name, if there's a close one.
+    pub suggestion: Option<&'static str>,
+}
+
+impl Display for UnknownField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown field `{}`", self.path)?;
+        if let Some(s) = self.suggestion {
+            write!(f, ", did you mean `{s}`?")?;
+        }
+        Ok(())
+    }
+}
+
+/// Deserialize metadata, tracking the path to any error. If `strict`, fields
+/// that aren't part of `M` are returned as errors instead of being ignored.
+pub fn deserialize_meta<'de, D, M>(
+    deserializer: D,
+    strict: bool,
+) -> Result<Result<M, Vec<UnknownField>>, serde_path_to_error::Error<D::Error>>
+where
+    D: Deserializer<'de>,
+    M: DeserializeOwned,
+{
+    let mut unknown = vec![];
+    let meta: M = serde_path_to_error::deserialize(serde_ignored::Deserializer::new(
+        deserializer,
+        &mut |p: serde_ignored::Path<'_>| unknown.push(p.to_string()),
+    ))?;
+
+    if !strict || unknown.is_empty() {
+        return Ok(Ok(meta));
+    }
+
+    let known = field_names::<M>();
+    Ok(Err(unknown
+        .into_iter()
+        .map(|path| UnknownField {
+            // Only top-level fields are known, so don't guess for nested ones
+            suggestion: if path.contains('.') {
+                None
+            } else {
+                closest(&path, known)
+            },
+            path,
+        })
+        .collect()))
+}
+
+/// The most similar candidate to `name`, if any are similar enough.
+fn closest(name: &str, candidates: &[&'static str]) -> Option<&'static str> {
+    let max_distance = (name.len() / 3).max(2);
+    candidates
+        .iter()
+        .map(|c| (strsim::damerau_levenshtein(name, c), *c))
+        .filter(|(d, _)| *d <= max_distance)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c)
+}
+
+/// Names of the top-level fields of a struct, found by asking it to
+/// deserialize itself from something that records what it asks for.
+fn field_names<M: DeserializeOwned>() -> &'static [&'static str] {
+    struct FieldRecorder<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de, 'a> Deserializer<'de> for FieldRecorder<'a> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("recorded fields"))
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    M::deserialize(FieldRecorder(&mut fields)).ok();
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use crate::model::metadata::Post;
+
+    use super::*;
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Meta {
+        title: String,
+        tagline: Option<String>,
+        tags: Vec<String>,
+        date: Dates,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Dates {
+        created: String,
+    }
+
+    fn load(yaml: &str, strict: bool) -> Result<Meta, Vec<UnknownField>> {
+        deserialize_meta(serde_yaml::Deserializer::from_str(yaml), strict).unwrap()
+    }
+
+    #[test]
+    pub fn lenient_mode_ignores_unknown_fields() {
+        let yaml = "title: x\ntag_line: y\ntags: []\ndate:\n  created: z\n";
+
+        assert!(load(yaml, false).is_ok());
+    }
+
+    #[test]
+    pub fn strict_mode_reports_unknown_fields_with_suggestions() {
+        let yaml = "title: x\ntag_line: y\ntag: []\ntags: []\ndate:\n  created: z\n  creatd: z\n  fnord: 1\n";
+
+        let errors = load(yaml, true).unwrap_err();
+
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec![
+                "unknown field `tag_line`, did you mean `tagline`?",
+                "unknown field `tag`, did you mean `tags`?",
+                "unknown field `date.creatd`",
+                "unknown field `date.fnord`",
+            ]
+        );
+    }
+
+    #[test]
+    pub fn finds_field_names_of_real_metadata() {
+        let fields = field_names::<Post>();
+
+        assert!(fields.contains(&"tagline"), "fields = {fields:?}");
+        assert_eq!(closest("reply-to", fields), Some("reply_to"));
+        assert_eq!(closest("fnord", fields), None);
+    }
+}
@@ -1,3 +1,7 @@
+use std::ops::Range;
+
+use serde::de::IgnoredAny;
+
 /// Split a filename's last file extension off, returning both.
 ///
 /// ```rust
@@ -11,3 +15,117 @@ pub fn split_extension(pathname: &str) -> (&str, &str) {
         None => (pathname, ""),
     }
 }
+
+/// A language that frontmatter can be written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrontmatterFormat {
+    /// Between `---` lines.
+    Yaml,
+
+    /// Between `+++` lines.
+    Toml,
+
+    /// Between `;;;` lines, or a bare object at the start of the file.
+    Json,
+}
+
+/// Frontmatter at the start of a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frontmatter<'a> {
+    pub format: FrontmatterFormat,
+
+    /// The frontmatter without its delimiters, with everything before it in
+    /// the file blanked out to whitespace. Positions in it are the same as in
+    /// the file, so parse errors point at the right place.
+    pub data: String,
+
+    /// The rest of the file.
+    pub body: &'a str,
+}
+
+/// Find the frontmatter of a file, returning its format, where its data is,
+/// and where it ends, including delimiters.
+fn find_frontmatter(raw: &str) -> Option<(FrontmatterFormat, Range<usize>, usize)> {
+    if raw.starts_with('{') {
+        let mut values = serde_json::Deserializer::from_str(raw).into_iter::<IgnoredAny>();
+        // If it's invalid, take the whole file so parsing it again shows where
+        let end = match values.next() {
+            Some(Ok(_)) => values.byte_offset(),
+            _ => raw.len(),
+        };
+        return Some((FrontmatterFormat::Json, 0..end, end));
+    }
+
+    let mut lines = raw.split_inclusive('\n');
+    let first = lines.next()?;
+    let (format, delimiter) = match first.trim_end() {
+        "---" => (FrontmatterFormat::Yaml, "---"),
+        "+++" => (FrontmatterFormat::Toml, "+++"),
+        ";;;" => (FrontmatterFormat::Json, ";;;"),
+        _ => return None,
+    };
+
+    let mut end = first.len();
+    for line in lines {
+        if line.trim_end() == delimiter {
+            return Some((format, first.len()..end, end + line.len()));
+        }
+        end += line.len();
+    }
+    None
+}
+
+/// Split a file into its frontmatter, including delimiters, and the rest.
+pub fn split_frontmatter(raw: &str) -> (&str, &str) {
+    match find_frontmatter(raw) {
+        Some((_, _, end)) => raw.split_at(end),
+        None => ("", raw),
+    }
+}
+
+/// Find and extract the frontmatter of a file, in any supported format.
+pub fn parse_frontmatter(raw: &str) -> Option<Frontmatter<'_>> {
+    let (format, data, end) = find_frontmatter(raw)?;
+    let blanked = raw[..data.start]
+        .chars()
+        .map(|c| if c == '\n' { '\n' } else { ' ' });
+
+    Some(Frontmatter {
+        format,
+        data: blanked.chain(raw[data].chars()).collect(),
+        body: &raw[end..],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn detects_frontmatter_formats() {
+        let yaml = parse_frontmatter("---\na: 1\n---\nbody").unwrap();
+        let toml = parse_frontmatter("+++\na = 1\n+++\n\nbody").unwrap();
+        let json = parse_frontmatter(";;;\n{\"a\": 1}\n;;;\nbody").unwrap();
+        let bare = parse_frontmatter("{\n  \"a\": {\"b\": \"}\"}\n}\nbody").unwrap();
+
+        assert_eq!(yaml.format, FrontmatterFormat::Yaml);
+        assert_eq!((yaml.data.as_str(), yaml.body), ("   \na: 1\n", "body"));
+        assert_eq!(toml.format, FrontmatterFormat::Toml);
+        assert_eq!((toml.data.as_str(), toml.body), ("   \na = 1\n", "\nbody"));
+        assert_eq!(json.format, FrontmatterFormat::Json);
+        assert_eq!(json.data, "   \n{\"a\": 1}\n");
+        assert_eq!(bare.format, FrontmatterFormat::Json);
+        assert_eq!(bare.body, "\nbody");
+        assert_eq!(parse_frontmatter("# Just a heading\n---\n"), None);
+        assert_eq!(parse_frontmatter("---\nnever closed\n"), None);
+    }
+
+    #[test]
+    pub fn splits_frontmatter_with_delimiters() {
+        assert_eq!(
+            split_frontmatter("+++\na = 1\n+++\nbody"),
+            ("+++\na = 1\n+++\n", "body")
+        );
+        assert_eq!(split_frontmatter("body"), ("", "body"));
+    }
+}
@@ -1,24 +1,33 @@
+use std::process::ExitCode;
+
 use clap::Parser;
-use render::output::build_static_site;
+use tracing::error;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
+mod check;
 mod cli;
 mod date_sort;
+mod deploy;
+mod diagnostics;
 mod errors;
+mod gc;
 mod load;
 mod media;
 mod model;
 mod random_coloring;
 mod render;
+mod scaffold;
+mod schema;
 mod templates;
 mod transform;
 mod upload;
 
 #[tokio::main(flavor = "current_thread")]
-async fn main() {
+async fn main() -> ExitCode {
     let args = cli::TopLevel::parse();
     tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer())
+        // Keep stdout for output that other programs read, like diagnostics
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
         .with(
             EnvFilter::builder()
                 .with_default_directive("info".parse().unwrap())
@@ -26,13 +35,34 @@ async fn main() {
         )
         .init();
 
-    _main(args).await.unwrap();
+    match _main(args).await {
+        Ok(code) => code,
+        Err(e) => {
+            error!("{e:?}");
+            ExitCode::FAILURE
+        }
+    }
 }
 
-async fn _main(args: cli::TopLevel) -> anyhow::Result<()> {
+async fn _main(args: cli::TopLevel) -> anyhow::Result<ExitCode> {
     match args.command {
         cli::Subcommand::Build(b) => {
-            build_static_site(b.src, b.out, b.script_assets).await?;
+            b.run().await?;
+        }
+        cli::Subcommand::Check(c) => {
+            return c.run().await;
+        }
+        cli::Subcommand::Deploy(d) => {
+            d.run().await?;
+        }
+        cli::Subcommand::Media(cli::MediaCommand::Gc(g)) => {
+            g.run().await?;
+        }
+        cli::Subcommand::New(n) => {
+            n.run()?;
+        }
+        cli::Subcommand::Schema(s) => {
+            s.run()?;
         }
         cli::Subcommand::Upload(u) => {
             u.run().await?;
@@ -40,5 +70,5 @@ async fn _main(args: cli::TopLevel) -> anyhow::Result<()> {
         cli::Subcommand::Watch(_) => todo!(),
     }
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
@@ -1,10 +1,17 @@
-use std::io::Read;
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    path::{Path, PathBuf},
+};
 
 use mime::Mime;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use tracing::debug;
 use vfs::{AltrootFS, VfsPath};
 
+use crate::transform::remote::RemoteImages;
+
 pub trait Uploadable {
     fn as_media(&self) -> anyhow::Result<Media>;
 }
@@ -28,6 +35,19 @@ impl Uploadable for Media {
     }
 }
 
+impl Uploadable for FileUploadable {
+    fn as_media(&self) -> anyhow::Result<Media> {
+        let mut buf = vec![];
+        self.path.open_file()?.read_to_end(&mut buf)?;
+
+        Ok(Media {
+            filename: self.filename.clone(),
+            mimetype: self.mimetype.clone(),
+            body: buf,
+        })
+    }
+}
+
 /// Upload from a slice in memory.
 #[derive(Clone)]
 pub struct Media {
@@ -36,13 +56,57 @@ pub struct Media {
     pub body: Vec<u8>,
 }
 
-/// Upload from a slice in memory.
+impl Media {
+    /// Content-addressed path to store this media at, like `<sha256>/<filename>`.
+    pub fn key(&self) -> String {
+        let b16 = content_hash(&self.body);
+        match &self.filename {
+            Some(n) => format!("{}/{}", b16, n),
+            None => b16,
+        }
+    }
+}
+
+/// Upload from a file that has already been stored.
 pub struct FileUploadable {
     pub filename: Option<String>,
     pub mimetype: Option<Mime>,
     pub path: VfsPath,
 }
 
+/// Lowercase hex SHA-256 of some data, used as the key for content-addressed files.
+pub fn content_hash(body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(body);
+    base16::encode_lower(&hasher.finalize())
+}
+
+/// Where the build writes its [MediaManifest] by default. It goes next to the
+/// output directory rather than inside it, so that it never gets deployed.
+pub fn default_manifest_path(out: &Path) -> std::io::Result<PathBuf> {
+    let out = std::path::absolute(out)?.components().collect::<PathBuf>();
+    let mut path = out.into_os_string();
+    path.push(".media.json");
+    Ok(path.into())
+}
+
+/// Every piece of media stored during a build, by its path in storage.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+pub struct MediaManifest {
+    pub media: BTreeMap<String, MediaEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MediaEntry {
+    pub sha256: String,
+    pub filename: Option<String>,
+    pub mimetype: Option<String>,
+    pub size: u64,
+
+    /// Source paths of the documents that use this media.
+    pub referenced_by: Vec<String>,
+}
+
 /// A [MediaRegistry] that stores uploaded media on a filesystem.
 pub struct MediaRegistry {
     /// URL prefix for every uploaded file
@@ -51,8 +115,11 @@ pub struct MediaRegistry {
     /// Where to store the files
     storage_root: VfsPath,
 
-    /// List of all currently-uploaded files
-    files: std::sync::Mutex<Vec<FileUploadable>>,
+    /// Every currently-uploaded file, by its content-addressed key
+    files: std::sync::Mutex<BTreeMap<String, FileUploadable>>,
+
+    /// Where to get remote images from, if they should be hosted locally
+    remote: Option<RemoteImages>,
 }
 
 impl MediaRegistry {
@@ -67,29 +134,71 @@ impl MediaRegistry {
             url_prefix,
             storage_root: VfsPath::new(AltrootFS::new(backing)),
             files: Default::default(),
+            remote: None,
+        }
+    }
+
+    /// Also host copies of remote images referenced in content.
+    pub fn with_remote(self, remote: RemoteImages) -> Self {
+        Self {
+            remote: Some(remote),
+            ..self
         }
     }
 
+    pub fn remote(&self) -> Option<&RemoteImages> {
+        self.remote.as_ref()
+    }
+
     /// Consume the VfsMediaRegistry, and returns a list of every file that has been stored.
-    #[allow(dead_code)]
     pub fn into_files(self) -> Vec<FileUploadable> {
-        self.files.into_inner().unwrap()
+        self.files.into_inner().unwrap().into_values().collect()
+    }
+
+    /// Describe every file stored so far, and which of the given documents use it.
+    ///
+    /// Documents are given as pairs of their source path and rendered HTML.
+    pub fn manifest<'a>(
+        &self,
+        documents: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> anyhow::Result<MediaManifest> {
+        let documents = documents.into_iter().collect::<Vec<_>>();
+        let mut manifest = MediaManifest::default();
+
+        for f in self.files.lock().unwrap().values() {
+            let key = f.path.as_str().trim_start_matches('/');
+            let url = format!("{}/{}", self.url_prefix, key);
+            let entry = MediaEntry {
+                sha256: key.split('/').next().unwrap_or_default().to_owned(),
+                filename: f.filename.clone(),
+                mimetype: f.mimetype.as_ref().map(|m| m.essence_str().to_owned()),
+                size: f.path.metadata()?.len,
+                referenced_by: documents
+                    .iter()
+                    .filter(|(_, html)| html.contains(&url))
+                    .map(|(source, _)| source.to_string())
+                    .collect(),
+            };
+            manifest.media.insert(key.to_owned(), entry);
+        }
+
+        Ok(manifest)
     }
 
     pub fn upload_media(&self, media: impl Uploadable) -> anyhow::Result<String> {
         let media = media.as_media()?;
 
-        let mut hasher = Sha256::new();
-        hasher.update(&media.body);
-        let hash = hasher.finalize();
-        let b16 = base16::encode_lower(&hash);
+        let path = media.key();
+        let url = format!("{}/{}", self.url_prefix, path);
 
-        debug!(filename = media.filename, size = media.body.len(), sha = %b16, "Adding new media");
+        // Keys are content-addressed, so media used in many places is only stored once
+        let mut files = self.files.lock().unwrap();
+        if files.contains_key(&path) {
+            debug!(%path, "Media already stored");
+            return Ok(url);
+        }
 
-        let path = match &media.filename {
-            Some(n) => format!("{}/{}", b16, n),
-            None => b16,
-        };
+        debug!(filename = media.filename, size = media.body.len(), %path, "Adding new media");
 
         let storage_path = self.storage_root.join(&path)?;
         storage_path.parent().create_dir_all()?;
@@ -101,8 +210,26 @@ impl MediaRegistry {
             path: storage_path,
         };
 
-        self.files.lock().unwrap().push(fu);
+        files.insert(path, fu);
 
-        Ok(format!("{}/{}", self.url_prefix, path))
+        Ok(url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn manifest_goes_next_to_the_output() {
+        let cwd = std::env::current_dir().unwrap();
+
+        for out in ["out", "out/", "./out/."] {
+            assert_eq!(
+                default_manifest_path(Path::new(out)).unwrap(),
+                cwd.join("out.media.json"),
+                "out = {out}"
+            );
+        }
     }
 }
@@ -1,16 +1,26 @@
 use std::{cmp::Ordering, fmt::Display};
 
 use chrono::NaiveDate;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// A computer that has been owned.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct Computer {
+    /// Display name of the computer.
     pub name: String,
+
+    /// Hostname the computer goes by on the network, if any.
     pub hostname: Option<String>,
+
+    /// The URL path component for this computer's page.
     pub slug: String,
+
     pub date: ComputerDates,
     pub status: Status,
     pub specs: Specs,
+
+    /// Why the computer is no longer in use.
     pub decom_reason: Option<String>,
 }
 
@@ -34,7 +44,9 @@ impl Computer {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, JsonSchema,
+)]
 pub enum Status {
     #[serde(rename = "in-use")]
     InUse,
@@ -60,16 +72,23 @@ impl Display for Status {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct ComputerDates {
+    /// When the computer was acquired.
     pub acquired: NaiveDate,
+
+    /// When the computer stopped being used. Leave blank if still in use.
     pub decomissioned: Option<NaiveDate>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct Specs {
+    /// Form factor, like laptop or desktop.
     pub r#type: String,
+
+    /// Make and model.
     pub model: String,
+
     pub cpu: String,
     pub ram: Ram,
     pub storage: Vec<Storage>,
@@ -77,15 +96,23 @@ pub struct Specs {
     pub motherboard: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct Storage {
+    /// Kind of drive, like SSD or HDD.
     pub r#type: String,
+
+    /// Capacity, like `512GB`.
     pub size: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct Ram {
+    /// Capacity, like `16GB`.
     pub size: String,
+
+    /// Generation, like `DDR4`.
     pub gen: String,
+
+    /// Clock speed, like `3200MHz`.
     pub speed: Option<String>,
 }
@@ -1,5 +1,6 @@
 use chrono::{DateTime, Datelike, FixedOffset, NaiveDate};
 use csscolorparser::Color;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -9,7 +10,7 @@ use crate::{
 
 use super::tag::Taggable;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct Post {
     /// Title of the post.
     pub title: String,
@@ -41,6 +42,7 @@ pub struct Post {
     pub author: Option<Author>,
 
     /// Accent color. If null, it will be randomly picked based on the slug.
+    #[schemars(with = "Option<String>")]
     pub color: Option<Color>,
 }
 
@@ -68,7 +70,7 @@ impl Taggable for Post {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct PostDates {
     /// When this document was completed, but not necessarily published. However,
     /// it is usually the same date as the publish date.
@@ -81,7 +83,7 @@ pub struct PostDates {
     pub updated: Option<DateTime<FixedOffset>>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct Author {
     /// Name of the author.
     pub name: String,
@@ -90,7 +92,7 @@ pub struct Author {
     pub biography: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct Project {
     /// Title of the project.
     pub title: String,
@@ -112,11 +114,12 @@ pub struct Project {
     pub url: ProjectUrls,
 
     /// Accent color. If null, it will be randomly picked based on the slug.
+    #[schemars(with = "Option<String>")]
     pub color: Option<Color>,
 }
 
 /// A generic page.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct ArbitraryPage {
     /// Title of the page.
     pub title: String,
@@ -142,20 +145,22 @@ pub struct ArbitraryPage {
         default,
         deserialize_with = "crate::model::util::permissive_vec::deserialize"
     )]
+    #[schemars(with = "crate::model::util::permissive_vec::PermissiveVec<String>")]
     pub navbar_path: Vec<String>,
 
     /// Accent color. If null, it will be randomly picked based on the slug.
+    #[schemars(with = "Option<String>")]
     pub color: Option<Color>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, JsonSchema)]
 pub enum ArbitraryPageFormat {
     /// Longform container (what blogs and projects have)
     #[default]
     Longform,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, JsonSchema)]
 pub struct PostSlug {
     pub date: Option<NaiveDate>,
     #[serde(default)]
@@ -163,18 +168,20 @@ pub struct PostSlug {
     pub name: String,
 }
 
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize, Default, JsonSchema)]
 pub struct ProjectUrls {
     #[serde(
         default,
         deserialize_with = "crate::model::util::permissive_vec::deserialize"
     )]
+    #[schemars(with = "crate::model::util::permissive_vec::PermissiveVec<String>")]
     pub site: Vec<String>,
 
     #[serde(
         default,
         deserialize_with = "crate::model::util::permissive_vec::deserialize"
     )]
+    #[schemars(with = "crate::model::util::permissive_vec::PermissiveVec<String>")]
     pub source: Vec<String>,
 }
 
@@ -194,7 +201,7 @@ impl Taggable for Project {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct ProjectDates {
     /// When the project was started.
     pub started: NaiveDate,
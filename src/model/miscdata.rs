@@ -1,26 +1,44 @@
 use chrono::{DateTime, FixedOffset};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// 88x31 button representation
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct Button88x31 {
+    /// URL of the button image.
     pub img: String,
+
+    /// Tooltip shown when hovering over the button.
     pub title: Option<String>,
+
+    /// Alt text of the button image.
     pub alt: Option<String>,
+
+    /// What the button links to, if anything.
     pub href: Option<String>,
+
+    /// JavaScript to run when the button is clicked.
     pub onclick: Option<String>,
 }
 
 /// Webring representation
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct Webring {
+    /// URL of the previous site in the ring.
     pub prev: String,
+
+    /// URL of the next site in the ring.
     pub next: String,
+
+    /// HTML shown between the previous and next links, usually the ring's name.
     pub html: String,
+
+    /// Whether membership in the ring is still waiting to be approved.
     pub pending: bool,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// An item in the navbar, possibly with a dropdown of more items.
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct NavbarItem {
     /// The HTML that should show up in the li where this item goes.
     pub display: String,
@@ -36,13 +54,20 @@ pub struct NavbarItem {
         default,
         deserialize_with = "crate::model::util::permissive_vec::deserialize"
     )]
+    #[schemars(with = "crate::model::util::permissive_vec::PermissiveVec<NavbarItem>")]
     pub children: Vec<NavbarItem>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// A short announcement shown on the homepage.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct NewsItem {
+    /// Headline of the item, if any.
     pub title: Option<String>,
+
+    /// When this happened.
     pub time: DateTime<FixedOffset>,
+
+    /// HTML body of the item.
     pub content: String,
 }
 
@@ -1,10 +1,18 @@
 pub mod metadata;
 pub mod computers;
 mod miscdata;
+mod routes;
 mod site_data;
+mod site_settings;
+mod stats;
+mod storage;
 mod tag;
 mod util;
 
 pub use miscdata::*;
+pub use routes::*;
 pub use site_data::*;
+pub use site_settings::*;
+pub use stats::*;
+pub use storage::*;
 pub use tag::*;
@@ -0,0 +1,162 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use vfs::VfsPath;
+
+use super::SiteData;
+
+/// What an output path gets rendered from.
+#[derive(Clone, Debug)]
+pub enum RouteSource {
+    /// A page rendered from a content file.
+    Document(VfsPath),
+
+    /// A page generated from the whole site, like an index.
+    Generated(&'static str),
+}
+
+impl Display for RouteSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RouteSource::Document(p) => write!(f, "{}", p.as_str()),
+            RouteSource::Generated(name) => write!(f, "the {name}"),
+        }
+    }
+}
+
+/// Two things that want to be rendered to the same place.
+#[derive(thiserror::Error, Debug)]
+#[error("{route} is already rendered from {existing}")]
+pub struct RouteCollision {
+    pub route: String,
+    pub existing: String,
+
+    /// The document that lost.
+    pub path: VfsPath,
+}
+
+/// Every output path, and what it gets rendered from.
+#[derive(Default, Clone, Debug)]
+pub struct RouteTable {
+    routes: BTreeMap<String, RouteSource>,
+}
+
+impl RouteTable {
+    /// Build the table, returning every document whose route was already taken.
+    pub fn new(sd: &SiteData) -> (Self, Vec<RouteCollision>) {
+        let mut table = Self::default();
+        let mut collisions = vec![];
+
+        for (route, name) in [
+            ("/", "homepage"),
+            ("/blog", "blog index"),
+            ("/computers", "computer index"),
+            ("/projects", "project index"),
+            ("/stats", "stats page"),
+            ("/feed.xml", "RSS feed"),
+            ("/static", "media directory"),
+        ] {
+            table
+                .routes
+                .insert(route.into(), RouteSource::Generated(name));
+        }
+        for slug in sd.tags.keys() {
+            table.routes.insert(
+                normalize_route(&format!("/t/{slug}")),
+                RouteSource::Generated("tag page"),
+            );
+        }
+
+        let mut documents = vec![];
+        documents.extend(sd.posts.iter().map(|d| (d.meta().href(), &d.document.path)));
+        documents.extend(
+            sd.projects
+                .iter()
+                .map(|d| (d.meta().href(), &d.document.path)),
+        );
+        documents.extend(
+            sd.computers
+                .iter()
+                .map(|d| (d.meta().href(), &d.document.path)),
+        );
+        documents.extend(
+            sd.pages
+                .iter()
+                .map(|d| (d.meta().slug.clone(), &d.document.path)),
+        );
+        for (href, path) in documents {
+            let route = normalize_route(&href);
+            match table.routes.get(&route) {
+                Some(existing) => collisions.push(RouteCollision {
+                    existing: existing.to_string(),
+                    route,
+                    path: path.clone(),
+                }),
+                None => {
+                    table
+                        .routes
+                        .insert(route, RouteSource::Document(path.clone()));
+                }
+            }
+        }
+
+        (table, collisions)
+    }
+
+    pub fn get(&self, href: &str) -> Option<&RouteSource> {
+        self.routes.get(&normalize_route(href))
+    }
+}
+
+/// Make equivalent hrefs compare equal, like `blog/` and `/blog`.
+pub fn normalize_route(href: &str) -> String {
+    let path = href.trim_end_matches("index.html");
+    format!("/{}", path.trim_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use vfs::MemoryFS;
+
+    use crate::{load::site_data::SiteDataLoadError, media::MediaRegistry};
+
+    use super::*;
+
+    #[tokio::test]
+    pub async fn reports_colliding_documents() {
+        let content = VfsPath::new(MemoryFS::new());
+        let write = |path: &str, body: &str| {
+            let p = content.join(path).unwrap();
+            p.parent().create_dir_all().unwrap();
+            p.create_file().unwrap().write_all(body.as_bytes()).unwrap();
+        };
+        let post = "---\ntitle: Hi\nslug:\n  date: 2024-01-01\n  name: hi\ndate:\n  created: 2024-01-01T00:00:00Z\n  published: 2024-01-01T00:00:00Z\n---\n";
+        write("blog/2024/01/01/0/hi/index.md", post);
+        write("blog/old/hi.md", post);
+        write(
+            "pages/blog.md",
+            "---\ntitle: Blog\ntags: []\nslug: /blog/\n---\n",
+        );
+        write("pages/ok.md", "---\ntitle: Ok\ntags: []\nslug: /ok\n---\n");
+        for dir in ["projects", "computers", "settings"] {
+            content.join(dir).unwrap().create_dir_all().unwrap();
+        }
+
+        let media = MediaRegistry::new("/static".into(), VfsPath::new(MemoryFS::new()));
+        let Err(SiteDataLoadError::UserError(errors)) = SiteData::load(content, &media).await
+        else {
+            panic!("expected collisions");
+        };
+
+        let mut messages = errors
+            .into_iter()
+            .map(|e| format!("{}: {}", e.path.as_str(), e.error))
+            .collect::<Vec<_>>();
+        messages.sort();
+        assert_eq!(messages.len(), 2, "messages = {messages:#?}");
+        assert!(messages[0].contains("/2024/01/01/0/hi is already rendered from /blog/"));
+        assert_eq!(
+            messages[1],
+            "/pages/blog.md: Route collision: /blog is already rendered from the blog index"
+        );
+    }
+}
@@ -1,5 +1,7 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
+use chrono::Datelike;
+use itertools::Itertools;
 use vfs::VfsPath;
 
 use crate::{
@@ -8,14 +10,17 @@ use crate::{
         site_data::{SiteDataLoadError, SiteDataLoader},
     },
     media::MediaRegistry,
-    transform::statistics::count_swears,
+    transform::{
+        links::find_internal_hrefs,
+        statistics::{count_terms, strip_tags, DocumentStatistics},
+    },
 };
 
 use super::{
-    computers::Computer,
+    computers::{Computer, Status},
     metadata::{ArbitraryPage, Post, Project},
     tag::TagSettings,
-    Button88x31, NavbarItem, NewsItem, Webring,
+    Button88x31, NavbarItem, NewsItem, RouteTable, StatsSettings, TermCounter, Webring,
 };
 
 pub type TagMap = HashMap<String, TagSettings>;
@@ -31,17 +36,76 @@ pub struct SiteData {
     pub navbar: Vec<NavbarItem>,
     pub buttons: Vec<Button88x31>,
     pub webrings: Vec<Webring>,
+    pub stats: StatsSettings,
     pub extra_head: String,
+
+    /// What every output path is rendered from.
+    pub routes: RouteTable,
+
+    /// Hosted copies of remote images used by templates, by their original URL.
+    pub mirrored_images: HashMap<String, String>,
 }
 
 #[derive(Default, Clone)]
 pub struct SiteIndex<'a> {
     pub tag_to_posts: HashMap<&'a str, Vec<&'a FullyLoadedDocument<Post>>>,
     pub tag_to_projects: HashMap<&'a str, Vec<&'a FullyLoadedDocument<Project>>>,
-    pub swear_count: HashMap<&'a str, usize>,
+    pub term_counts: Vec<TermCounts<'a>>,
+    pub post_stats: DocumentStatistics,
+    pub project_stats: DocumentStatistics,
+    pub page_stats: DocumentStatistics,
+
+    /// Number of posts published in each (year, month).
+    pub posts_per_month: BTreeMap<(i32, u32), usize>,
+
+    /// Number of posts and projects with each tag, most-used first.
+    pub tag_counts: Vec<(&'a str, usize)>,
+
+    pub computer_stats: ComputerStatistics<'a>,
+
+    /// Documents linking to each href.
+    pub backlinks: HashMap<&'a str, Vec<Backlink<'a>>>,
+}
+
+/// A document that links to another document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Backlink<'a> {
+    pub title: &'a str,
+    pub href: String,
+}
+
+/// Results of running a [TermCounter] over the site.
+#[derive(Clone, Debug)]
+pub struct TermCounts<'a> {
+    pub counter: &'a TermCounter,
+    pub counts: HashMap<&'a str, usize>,
+}
+
+impl<'a> SiteIndex<'a> {
+    pub fn backlinks(&self, href: &str) -> &[Backlink<'a>] {
+        self.backlinks.get(href).map_or(&[], |bs| bs.as_slice())
+    }
+}
+
+impl TermCounts<'_> {
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct ComputerStatistics<'a> {
+    pub by_status: BTreeMap<&'a Status, usize>,
+    pub by_type: BTreeMap<&'a str, usize>,
 }
 
 impl SiteData {
+    /// URL to use for an image in a template, which is a hosted copy if the
+    /// image was mirrored.
+    pub fn image_url<'a>(&'a self, url: &'a str) -> &'a str {
+        self.mirrored_images.get(url).map_or(url, |u| u.as_str())
+    }
+
     pub async fn load(path: VfsPath, media: &MediaRegistry) -> Result<SiteData, SiteDataLoadError> {
         SiteDataLoader::new(path, media).load().await
     }
@@ -54,23 +118,107 @@ impl SiteData {
             out.tag_to_projects.insert(t, vec![]);
         }
 
+        out.term_counts = self
+            .stats
+            .counters
+            .iter()
+            .map(|counter| TermCounts {
+                counter,
+                counts: HashMap::new(),
+            })
+            .collect();
+
+        let mut count_terms_in = |html: &str| {
+            let text = strip_tags(html);
+            for tc in &mut out.term_counts {
+                for (s, c) in count_terms(&text, tc.counter) {
+                    *tc.counts.entry(s).or_default() += c;
+                }
+            }
+        };
+
+        for p in &self.posts {
+            count_terms_in(p.html());
+        }
+        for p in &self.projects {
+            count_terms_in(p.html());
+        }
+
         for p in &self.posts {
             for t in &p.meta().tags {
                 out.tag_to_posts.entry(t.as_str()).or_default().push(p);
             }
-            for (s, c) in count_swears(p.html()) {
-                *out.swear_count.entry(s).or_default() += c;
-            }
+            out.post_stats += *p.stats();
+
+            let published = p.meta().date.published;
+            *out.posts_per_month
+                .entry((published.year(), published.month()))
+                .or_default() += 1;
         }
 
         for p in &self.projects {
             for t in &p.meta().tags {
                 out.tag_to_projects.entry(t.as_str()).or_default().push(p);
             }
-            for (s, c) in count_swears(p.html()) {
-                *out.swear_count.entry(s).or_default() += c;
+            out.project_stats += *p.stats();
+        }
+
+        out.page_stats = self.pages.iter().map(|p| p.stats()).sum();
+
+        out.tag_counts = out
+            .tag_to_posts
+            .iter()
+            .map(|(t, ps)| {
+                (
+                    *t,
+                    ps.len() + out.tag_to_projects.get(t).map_or(0, |ps| ps.len()),
+                )
+            })
+            .filter(|(_, c)| *c != 0)
+            .sorted_by(|(ta, ca), (tb, cb)| cb.cmp(ca).then(ta.cmp(tb)))
+            .collect();
+
+        let posts = self
+            .posts
+            .iter()
+            .map(|p| (p.html(), &p.meta().title, p.meta().href()));
+        let projects = self
+            .projects
+            .iter()
+            .map(|p| (p.html(), &p.meta().title, p.meta().href()));
+        let pages = self
+            .pages
+            .iter()
+            .map(|p| (p.html(), &p.meta().title, p.meta().slug.clone()));
+        let computers = self
+            .computers
+            .iter()
+            .map(|c| (c.html(), &c.meta().name, c.meta().href()));
+        let linkers = posts.chain(projects).chain(pages).chain(computers);
+        for (html, title, href) in linkers {
+            for target in find_internal_hrefs(html).unique() {
+                if target == href {
+                    continue;
+                }
+                out.backlinks.entry(target).or_default().push(Backlink {
+                    title,
+                    href: href.clone(),
+                });
             }
         }
+        // Documents are loaded concurrently, so their order isn't stable
+        for links in out.backlinks.values_mut() {
+            links.sort_by(|a, b| a.href.cmp(&b.href));
+        }
+
+        for c in &self.computers {
+            let c = c.meta();
+            *out.computer_stats.by_status.entry(&c.status).or_default() += 1;
+            *out.computer_stats
+                .by_type
+                .entry(c.specs.r#type.as_str())
+                .or_default() += 1;
+        }
 
         out
     }
@@ -0,0 +1,30 @@
+use frunk::{Monoid, Semigroup};
+use serde::{Deserialize, Serialize};
+
+/// Settings about the site as a whole, loaded from `*.site.yml`.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+pub struct SiteSettings {
+    /// IANA name of the timezone that new documents are dated in, like
+    /// `America/Los_Angeles`. Defaults to the system timezone.
+    pub timezone: Option<String>,
+
+    /// Whether unknown fields in document frontmatter are errors, instead of
+    /// being ignored. Catches typos like `tag_line` for `tagline`.
+    pub strict_frontmatter: Option<bool>,
+}
+
+impl Semigroup for SiteSettings {
+    fn combine(&self, other: &Self) -> Self {
+        // other takes precedence over self
+        Self {
+            timezone: other.timezone.clone().or(self.timezone.clone()),
+            strict_frontmatter: other.strict_frontmatter.or(self.strict_frontmatter),
+        }
+    }
+}
+
+impl Monoid for SiteSettings {
+    fn empty() -> Self {
+        Default::default()
+    }
+}
@@ -0,0 +1,49 @@
+use frunk::{Monoid, Semigroup};
+use serde::{Deserialize, Serialize};
+
+/// Settings for the statistics page, loaded from `*.stats.yml`.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct StatsSettings {
+    /// Terms to count across all posts and projects.
+    #[serde(default)]
+    pub counters: Vec<TermCounter>,
+
+    /// ID of the counter to show on the homepage, if any.
+    pub homepage_counter: Option<String>,
+}
+
+/// A set of terms whose occurrences get counted across the site.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TermCounter {
+    /// ID to refer to this counter by, like `swears`.
+    pub id: String,
+
+    /// Human-readable name of this counter.
+    pub title: String,
+
+    /// Terms to count. Matches only whole words, unless the term ends with
+    /// `*`, in which case it matches any word starting with the term.
+    pub terms: Vec<String>,
+
+    /// Whether matching should be case-sensitive.
+    #[serde(default)]
+    pub case_sensitive: bool,
+}
+
+impl Semigroup for StatsSettings {
+    fn combine(&self, other: &Self) -> Self {
+        Self {
+            counters: self.counters.combine(&other.counters),
+            homepage_counter: other
+                .homepage_counter
+                .clone()
+                .or_else(|| self.homepage_counter.clone()),
+        }
+    }
+}
+
+impl Monoid for StatsSettings {
+    fn empty() -> Self {
+        Default::default()
+    }
+}
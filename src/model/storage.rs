@@ -0,0 +1,48 @@
+use frunk::{Monoid, Semigroup};
+use serde::{Deserialize, Serialize};
+
+/// Where uploaded files get stored, loaded from `*.storage.yml`.
+///
+/// Every field is optional, so that it can be overridden by command-line
+/// flags and environment variables.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+pub struct StorageSettings {
+    /// Name of the bucket to upload to.
+    pub bucket: Option<String>,
+
+    /// S3-compatible endpoint, like `https://s3.us-west-000.backblazeb2.com`.
+    pub endpoint: Option<String>,
+
+    /// Region name to sign requests with.
+    pub region: Option<String>,
+
+    /// Whether to address the bucket as `endpoint/bucket` instead of `bucket.endpoint`.
+    pub path_style: Option<bool>,
+
+    /// Template for the public URL of an uploaded object. May contain
+    /// `{endpoint}`, `{bucket}` and `{key}`.
+    pub public_url: Option<String>,
+
+    /// Prefix to put in front of every key.
+    pub key_prefix: Option<String>,
+}
+
+impl Semigroup for StorageSettings {
+    fn combine(&self, other: &Self) -> Self {
+        // other takes precedence over self
+        Self {
+            bucket: other.bucket.clone().or(self.bucket.clone()),
+            endpoint: other.endpoint.clone().or(self.endpoint.clone()),
+            region: other.region.clone().or(self.region.clone()),
+            path_style: other.path_style.or(self.path_style),
+            public_url: other.public_url.clone().or(self.public_url.clone()),
+            key_prefix: other.key_prefix.clone().or(self.key_prefix.clone()),
+        }
+    }
+}
+
+impl Monoid for StorageSettings {
+    fn empty() -> Self {
+        Default::default()
+    }
+}
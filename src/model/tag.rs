@@ -2,6 +2,7 @@ use std::collections::HashMap;
 
 use frunk::{Monoid, Semigroup};
 use palette::{convert::TryFromColor, Hsl, Srgb};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::random_coloring::{self, ColorProfileExt};
@@ -29,10 +30,16 @@ pub struct TagSettings {
     pub styling: TagStyling,
 }
 
-#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[derive(Serialize, Deserialize, Default, Clone, Debug, JsonSchema)]
 pub struct TagStyleDirectiveBody {
+    /// CSS color of the tag's text. Picked to contrast with `color` if not
+    /// provided.
     pub text_color: Option<Color>,
+
+    /// CSS background color of the tag. Randomly picked if not provided.
     pub color: Option<Color>,
+
+    /// Class to style the tag with in the stylesheet, instead of colors.
     pub class: Option<String>,
 }
 
@@ -86,15 +93,23 @@ impl Monoid for TagStyleDirectiveBody {
     }
 }
 
-#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+/// Styling applied to some tags.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, JsonSchema)]
 pub struct TagStyleDirective {
+    /// Slugs of the tags to style.
     pub tags: Vec<String>,
+
+    /// The styling to apply. Later directives override earlier ones.
     pub apply: TagStyleDirectiveBody,
 }
 
-#[derive(Serialize, Deserialize, Default, Clone)]
+/// Titles and styling of tags, loaded from `*.tag.yml`.
+#[derive(Serialize, Deserialize, Default, Clone, JsonSchema)]
 pub struct TagSettingsSheet {
+    /// Display titles of tags, keyed by slug. Tags without one are titled
+    /// with their slug.
     titles: HashMap<String, String>,
+
     styles: Vec<TagStyleDirective>,
 }
 
@@ -1,13 +1,18 @@
 pub mod permissive_vec {
+    use schemars::JsonSchema;
     use serde::{Deserialize, Deserializer, Serialize};
 
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, JsonSchema)]
     #[serde(untagged)]
-    enum VecOrSingle<T> {
+    pub enum VecOrSingle<T> {
         Single(T),
         Multiple(Vec<T>),
     }
 
+    /// What [deserialize] accepts, for describing it in schemas: nothing, one
+    /// item, or a list of them.
+    pub type PermissiveVec<T> = Option<VecOrSingle<T>>;
+
     pub fn deserialize<'de, T: Deserialize<'de>, D: Deserializer<'de>>(
         d: D,
     ) -> Result<Vec<T>, D::Error> {
@@ -3,16 +3,20 @@ use std::{ffi::OsString, fs::create_dir_all, path::Path};
 use maud::Render;
 use tokio::time::Instant;
 use tracing::{debug, info};
-use vfs::{PhysicalFS, VfsError, VfsPath};
+use vfs::{MemoryFS, PhysicalFS, VfsError, VfsPath};
 use walkdir::WalkDir;
 
 use crate::{
+    load::document::FullyLoadedDocument,
     media::MediaRegistry,
     model::SiteData,
     templates::{
         ArbitraryPageRender, BaseRenderer, BlogIndexPage, ComputerIndexPage, Homepage,
-        ProjectIndexPage, RenderComputer, RenderPost, RenderProject, TagPage,
+        ProjectIndexPage, RenderComputer, RenderPost, RenderProject, StatsPage, TagPage,
+        TEMPLATE_IMAGES,
     },
+    transform::remote::{mirror_image_urls, RemoteImages},
+    upload::{publish_media, S3Config},
 };
 
 use super::rss::make_rss;
@@ -21,7 +25,10 @@ use super::rss::make_rss;
 pub async fn build_static_site(
     content: impl AsRef<Path>,
     out: impl AsRef<Path>,
+    media_manifest: impl AsRef<Path>,
     script_assets: Option<impl AsRef<Path>>,
+    media_cdn: Option<S3Config>,
+    remote_images: Option<RemoteImages>,
 ) -> anyhow::Result<()> {
     let script_assets = script_assets.map(|s| s.as_ref().to_owned());
     info!(
@@ -41,16 +48,74 @@ pub async fn build_static_site(
     create_dir_all(out.as_ref())?;
     let out = VfsPath::new(PhysicalFS::new(out.as_ref()));
     let content = VfsPath::new(PhysicalFS::new(content.as_ref()));
+    let with_remote = |media: MediaRegistry| match remote_images {
+        Some(r) => media.with_remote(r),
+        None => media,
+    };
 
-    let media = MediaRegistry::new("/static".into(), out.join("static")?);
-    let sd = SiteData::load(content, &media).await?;
-    write_static_site(&sd, out, script_templates)?;
+    match media_cdn {
+        Some(config) => {
+            if !config.public_url.ends_with("{key}") {
+                anyhow::bail!("Public URL template must end with {{key}} to publish media");
+            }
+
+            // Media gets uploaded instead, so it never has to touch the output
+            let media = with_remote(MediaRegistry::new(
+                config.url_prefix(),
+                VfsPath::new(MemoryFS::new()),
+            ));
+            let sd = render_site(content, out, &media, script_templates).await?;
+            write_media_manifest(&sd, &media, media_manifest.as_ref())?;
+            publish_media(&config.bucket()?, &config, media.into_files()).await?;
+        }
+        None => {
+            let media = with_remote(MediaRegistry::new("/static".into(), out.join("static")?));
+            let sd = render_site(content, out, &media, script_templates).await?;
+            write_media_manifest(&sd, &media, media_manifest.as_ref())?;
+        }
+    }
 
     info!(elapsed = ?start.elapsed(), "Completed");
 
     Ok(())
 }
 
+/// Load the site from the content directory, and render it into the output directory.
+pub async fn render_site(
+    content: VfsPath,
+    out: VfsPath,
+    media: &MediaRegistry,
+    script_templates: Vec<String>,
+) -> anyhow::Result<SiteData> {
+    let mut sd = SiteData::load(content, media).await?;
+    sd.mirrored_images = mirror_image_urls(media, TEMPLATE_IMAGES).await;
+    write_static_site(&sd, out, script_templates)?;
+
+    Ok(sd)
+}
+
+/// Record which media the site uses, for `seams media gc`.
+fn write_media_manifest(sd: &SiteData, media: &MediaRegistry, path: &Path) -> anyhow::Result<()> {
+    let documents = (sd.posts.iter().map(source_and_html))
+        .chain(sd.projects.iter().map(source_and_html))
+        .chain(sd.pages.iter().map(source_and_html))
+        .chain(sd.computers.iter().map(source_and_html));
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    std::fs::write(
+        path,
+        serde_json::to_vec_pretty(&media.manifest(documents)?)?,
+    )?;
+    debug!(path = %path.display(), "Wrote media manifest");
+
+    Ok(())
+}
+
+fn source_and_html<M>(doc: &FullyLoadedDocument<M>) -> (&str, &str) {
+    (doc.document.path.as_str(), doc.html())
+}
+
 pub fn write_static_site(
     sd: &SiteData,
     outdir: VfsPath,
@@ -120,6 +185,8 @@ pub fn write_static_site(
         )?;
     }
 
+    write_markup(&outdir.join("stats")?, renderer.render_page(StatsPage))?;
+
     for p in &sd.pages {
         write_markup(
             &outdir.join(&p.meta().slug)?,
@@ -0,0 +1,263 @@
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, TimeZone, Timelike};
+use serde::Serialize;
+use vfs::VfsPath;
+
+use crate::model::{
+    computers::{Computer, ComputerDates, Ram, Specs, Status},
+    metadata::{ArbitraryPage, Post, PostDates, PostSlug, Project, ProjectDates},
+};
+
+/// Kinds of documents that can be scaffolded.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DocumentKind {
+    Post,
+    Project,
+    Page,
+    Computer,
+}
+
+/// Turn a title into something URL-friendly, like `my-cool-post`.
+pub fn slugify(title: &str) -> String {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// The lowest ordinal not yet used by a post on that day.
+pub fn next_ordinal(blog: &VfsPath, date: NaiveDate) -> anyhow::Result<u8> {
+    let day = blog.join(format!(
+        "{}/{:02}/{:02}",
+        date.year(),
+        date.month(),
+        date.day()
+    ))?;
+    if !day.exists()? {
+        return Ok(0);
+    }
+
+    let used = day
+        .read_dir()?
+        .filter_map(|p| p.filename().parse::<u8>().ok())
+        .collect::<Vec<_>>();
+    (0..=u8::MAX)
+        .find(|o| !used.contains(o))
+        .ok_or_else(|| anyhow::anyhow!("every ordinal on {date} is taken"))
+}
+
+/// Create a new document with valid frontmatter, returning its path.
+///
+/// `now` should already be in the site's timezone.
+pub fn scaffold<Tz: TimeZone>(
+    content: &VfsPath,
+    kind: DocumentKind,
+    title: &str,
+    now: DateTime<Tz>,
+) -> anyhow::Result<VfsPath> {
+    let now = now.fixed_offset().with_nanosecond(0).unwrap();
+    let today = now.date_naive();
+    let slug = slugify(title);
+    if slug.is_empty() {
+        anyhow::bail!("title {title:?} has nothing to make a slug out of");
+    }
+
+    let (path, frontmatter) = match kind {
+        DocumentKind::Post => {
+            let ordinal = next_ordinal(&content.join("blog")?, today)?;
+            let path = format!(
+                "blog/{}/{:02}/{:02}/{ordinal}/{slug}/index.md",
+                today.year(),
+                today.month(),
+                today.day()
+            );
+            (path, to_frontmatter(&new_post(title, slug, ordinal, now))?)
+        }
+        DocumentKind::Project => {
+            let path = format!(
+                "projects/{}-{:02}-{slug}/index.md",
+                today.year(),
+                today.month()
+            );
+            (path, to_frontmatter(&new_project(title, slug, today))?)
+        }
+        DocumentKind::Page => {
+            let path = format!("pages/{slug}.md");
+            (path, to_frontmatter(&new_page(title, slug))?)
+        }
+        DocumentKind::Computer => {
+            let path = format!("computers/{slug}.md");
+            (path, to_frontmatter(&new_computer(title, slug, today))?)
+        }
+    };
+
+    let path = content.join(path)?;
+    if path.exists()? {
+        anyhow::bail!("{} already exists", path.as_str());
+    }
+    path.parent().create_dir_all()?;
+    write!(path.create_file()?, "---\n{frontmatter}---\n\n")?;
+
+    Ok(path)
+}
+
+fn new_post(title: &str, slug: String, ordinal: u8, now: DateTime<FixedOffset>) -> Post {
+    Post {
+        title: title.into(),
+        tagline: None,
+        slug: PostSlug {
+            date: Some(now.date_naive()),
+            ordinal,
+            name: slug,
+        },
+        date: PostDates {
+            created: now,
+            published: now,
+            updated: None,
+        },
+        tags: vec![],
+        client: None,
+        reply_to: vec![],
+        location: None,
+        author: None,
+        color: None,
+    }
+}
+
+fn new_project(title: &str, slug: String, today: NaiveDate) -> Project {
+    Project {
+        title: title.into(),
+        tagline: None,
+        slug,
+        tags: vec![],
+        date: ProjectDates {
+            started: today,
+            finished: None,
+            sort_date: None,
+            published: None,
+        },
+        url: Default::default(),
+        color: None,
+    }
+}
+
+fn new_page(title: &str, slug: String) -> ArbitraryPage {
+    ArbitraryPage {
+        title: title.into(),
+        meta_description: None,
+        thumbnail: None,
+        tags: vec![],
+        slug: format!("/{slug}"),
+        format: Default::default(),
+        navbar_path: vec![],
+        color: None,
+    }
+}
+
+fn new_computer(title: &str, slug: String, today: NaiveDate) -> Computer {
+    Computer {
+        name: title.into(),
+        hostname: Some(slug.clone()),
+        slug,
+        date: ComputerDates {
+            acquired: today,
+            decomissioned: None,
+        },
+        status: Status::InUse,
+        specs: Specs {
+            r#type: String::new(),
+            model: String::new(),
+            cpu: String::new(),
+            ram: Ram {
+                size: String::new(),
+                gen: String::new(),
+                speed: None,
+            },
+            storage: vec![],
+            gpu: None,
+            motherboard: None,
+        },
+        decom_reason: None,
+    }
+}
+
+/// Serialize to YAML, leaving out everything that's null.
+fn to_frontmatter(meta: &impl Serialize) -> anyhow::Result<String> {
+    fn strip_nulls(v: &mut serde_yaml::Value) {
+        if let serde_yaml::Value::Mapping(m) = v {
+            m.retain(|_, v| !v.is_null());
+            m.values_mut().for_each(strip_nulls);
+        }
+    }
+
+    let mut value = serde_yaml::to_value(meta)?;
+    strip_nulls(&mut value);
+    Ok(serde_yaml::to_string(&value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::America::Los_Angeles;
+    use vfs::MemoryFS;
+
+    use crate::load::document::Document;
+
+    use super::*;
+
+    #[test]
+    pub fn slugifies_titles() {
+        assert_eq!(
+            slugify("Gaming on an Android VM!"),
+            "gaming-on-an-android-vm"
+        );
+        assert_eq!(slugify("  C++ -- the good parts "), "c-the-good-parts");
+    }
+
+    #[test]
+    pub fn scaffolds_posts_with_next_ordinal() {
+        let content = VfsPath::new(MemoryFS::new());
+        content
+            .join("blog/2024/03/09/0")
+            .unwrap()
+            .create_dir_all()
+            .unwrap();
+        let now = Los_Angeles.with_ymd_and_hms(2024, 3, 9, 22, 30, 0).unwrap();
+
+        let path = scaffold(&content, DocumentKind::Post, "Hello, World", now).unwrap();
+
+        assert_eq!(path.as_str(), "/blog/2024/03/09/1/hello-world/index.md");
+        let doc = Document::<Post>::load(path.clone()).unwrap();
+        assert_eq!(doc.meta.slug.ordinal, 1);
+        assert_eq!(
+            doc.meta.date.published.to_rfc3339(),
+            "2024-03-09T22:30:00-08:00"
+        );
+        assert!(scaffold(&content, DocumentKind::Post, "Hello, World", now)
+            .unwrap()
+            .as_str()
+            .contains("/2/"));
+    }
+
+    #[test]
+    pub fn scaffolds_valid_documents() {
+        let content = VfsPath::new(MemoryFS::new());
+        let now = Los_Angeles.with_ymd_and_hms(2024, 7, 1, 12, 0, 0).unwrap();
+
+        let project = scaffold(&content, DocumentKind::Project, "Segway Bot", now).unwrap();
+        let page = scaffold(&content, DocumentKind::Page, "About", now).unwrap();
+        let computer = scaffold(&content, DocumentKind::Computer, "Chungus", now).unwrap();
+
+        assert_eq!(project.as_str(), "/projects/2024-07-segway-bot/index.md");
+        Document::<Project>::load(project.clone()).unwrap();
+        assert_eq!(
+            Document::<ArbitraryPage>::load(page.clone())
+                .unwrap()
+                .meta
+                .slug,
+            "/about"
+        );
+        Document::<Computer>::load(computer.clone()).unwrap();
+        assert!(scaffold(&content, DocumentKind::Page, "About", now).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use schemars::{schema::RootSchema, schema_for};
+use serde_json::{Map, Value};
+
+use crate::model::{
+    computers::Computer,
+    metadata::{ArbitraryPage, Post, Project},
+    Button88x31, NavbarItem, NewsItem, TagSettingsSheet, Webring,
+};
+
+/// A JSON Schema for one kind of file, and the globs those files match.
+pub struct SchemaFile {
+    /// Name of the schema, used as its filename.
+    pub name: &'static str,
+
+    /// Where files using this schema are, relative to the workspace.
+    pub globs: &'static [&'static str],
+
+    pub schema: RootSchema,
+}
+
+impl SchemaFile {
+    pub fn filename(&self) -> String {
+        format!("{}.schema.json", self.name)
+    }
+}
+
+/// Schemas for every kind of document and settings file.
+///
+/// Documents only match metadata kept in separate `.yml` files. Frontmatter
+/// embedded in markdown isn't matched, since the VS Code YAML extension only
+/// validates YAML files.
+pub fn schemas() -> Vec<SchemaFile> {
+    vec![
+        SchemaFile {
+            name: "post",
+            globs: &["**/blog/**/*.yml", "**/blog/**/*.yaml"],
+            schema: schema_for!(Post),
+        },
+        SchemaFile {
+            name: "project",
+            globs: &["**/projects/**/*.yml", "**/projects/**/*.yaml"],
+            schema: schema_for!(Project),
+        },
+        SchemaFile {
+            name: "page",
+            globs: &["**/pages/**/*.yml", "**/pages/**/*.yaml"],
+            schema: schema_for!(ArbitraryPage),
+        },
+        SchemaFile {
+            name: "computer",
+            globs: &["**/computers/**/*.yml", "**/computers/**/*.yaml"],
+            schema: schema_for!(Computer),
+        },
+        SchemaFile {
+            name: "tag",
+            globs: &["**/*.tag.yml", "**/*.tag.yaml"],
+            schema: schema_for!(TagSettingsSheet),
+        },
+        SchemaFile {
+            name: "navbar",
+            globs: &["**/*.navbar.yml", "**/*.navbar.yaml"],
+            schema: schema_for!(Vec<NavbarItem>),
+        },
+        SchemaFile {
+            name: "news",
+            globs: &["**/*.news.yml", "**/*.news.yaml"],
+            schema: schema_for!(Vec<NewsItem>),
+        },
+        SchemaFile {
+            name: "88x31",
+            globs: &["**/*.88x31.yml", "**/*.88x31.yaml"],
+            schema: schema_for!(Vec<Button88x31>),
+        },
+        SchemaFile {
+            name: "webring",
+            globs: &["**/*.webring.yml", "**/*.webring.yaml"],
+            schema: schema_for!(Vec<Webring>),
+        },
+    ]
+}
+
+/// Add a `yaml.schemas` mapping for the VS Code YAML extension to some
+/// existing settings, keeping everything else in them.
+///
+/// `dir` is where the schemas were written, relative to the workspace.
+pub fn add_vscode_mappings(settings: &mut Map<String, Value>, dir: &str, schemas: &[SchemaFile]) {
+    let mappings = settings
+        .entry("yaml.schemas")
+        .or_insert_with(|| Value::Object(Map::new()));
+    if !mappings.is_object() {
+        *mappings = Value::Object(Map::new());
+    }
+    let mappings = mappings.as_object_mut().unwrap();
+
+    let dir = dir.trim_end_matches('/');
+    for s in schemas {
+        mappings.insert(
+            format!("{dir}/{}", s.filename()),
+            Value::from(s.globs.to_vec()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    pub fn doc_comments_become_descriptions() {
+        let post = schemas().into_iter().find(|s| s.name == "post").unwrap();
+        let post = serde_json::to_value(post.schema).unwrap();
+
+        assert_eq!(
+            post["properties"]["tagline"]["description"],
+            "Tagline of the post."
+        );
+        assert_eq!(post["required"], json!(["date", "slug", "title"]));
+    }
+
+    #[test]
+    pub fn navbar_children_can_be_one_or_many() {
+        let navbar = schemas().into_iter().find(|s| s.name == "navbar").unwrap();
+        let navbar = serde_json::to_value(navbar.schema).unwrap();
+
+        assert_eq!(navbar["type"], "array");
+        let children = &navbar["definitions"]["NavbarItem"]["properties"]["children"];
+        assert!(
+            children.to_string().contains("anyOf"),
+            "children = {children}"
+        );
+    }
+
+    #[test]
+    pub fn keeps_other_vscode_settings() {
+        let mut settings = json!({
+            "editor.tabSize": 2,
+            "yaml.schemas": { "other.json": "*.other.yml" },
+        });
+        add_vscode_mappings(settings.as_object_mut().unwrap(), "./schemas/", &schemas());
+
+        assert_eq!(settings["editor.tabSize"], 2);
+        assert_eq!(settings["yaml.schemas"]["other.json"], "*.other.yml");
+        assert_eq!(
+            settings["yaml.schemas"]["./schemas/tag.schema.json"],
+            json!(["**/*.tag.yml", "**/*.tag.yaml"])
+        );
+    }
+}
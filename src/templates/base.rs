@@ -5,6 +5,9 @@ use crate::{
     templates::{navbar::Navbar, util::RenderWebring},
 };
 
+/// Files that every page links to, but which are built separately from seams.
+pub const EXTERNAL_ASSETS: &[&str] = &["/styles.css", "/bundle.js"];
+
 /// Renders pages using the base template
 #[derive(Clone)]
 pub struct BaseRenderer<'a> {
@@ -5,9 +5,9 @@ use crate::{
     load::document::FullyLoadedDocument,
     model::{
         metadata::{Post, PostDates},
-        SiteData, SiteIndex, TagMap, TaggableExt,
+        Backlink, SiteData, SiteIndex, TagMap, TaggableExt,
     },
-    templates::util::{format_dt_html, tag_list},
+    templates::util::{backlinks_section, format_dt_html, format_reading_time, tag_list},
 };
 
 use super::{util::format_dt, BaseTemplatePage, PageMeta};
@@ -80,6 +80,7 @@ impl<'a> RenderPost<'a> {
             div .post-row .nsfw[meta.has_tag("nsfw")] {
                 div .datepane {
                     p .date { a href=(meta.href()) { (self.date()) } }
+                    p .reading-time { (self.reading_time()) }
                 }
                 div .itempane {
                     div .titlepane {
@@ -120,7 +121,7 @@ impl<'a> RenderPost<'a> {
 
                 footer {
                     (tag_list(tags, &meta.tags))
-                    p { (self.date()) }
+                    p { (self.date()) " · " (self.reading_time()) }
                 }
             }
         }
@@ -134,17 +135,19 @@ impl<'a> RenderPost<'a> {
         }
     }
 
-    pub fn page_content(&self, tags: &TagMap) -> Markup {
+    pub fn page_content(&self, tags: &TagMap, backlinks: &[Backlink]) -> Markup {
         html! {
             article .post-content {
                 header {
                     (self.title(false))
                     (self.tagline())
-                    p .date { (self.date()) }
+                    p .date { (self.date()) " · " span .reading-time { (self.reading_time()) } }
                     p { (tag_list(tags, &self.post.meta().tags)) }
                 }
 
                 (PreEscaped(&self.post.html()))
+
+                (backlinks_section(backlinks))
             }
         }
     }
@@ -170,6 +173,10 @@ impl<'a> RenderPost<'a> {
         }
     }
 
+    fn reading_time(&self) -> String {
+        format_reading_time(self.post.stats())
+    }
+
     fn date(&self) -> Markup {
         let PostDates {
             created: c_raw,
@@ -195,7 +202,8 @@ impl<'a> RenderPost<'a> {
 }
 
 impl BaseTemplatePage for RenderPost<'_> {
-    fn render_page(&self, sd: &SiteData, _si: &SiteIndex<'_>) -> (PageMeta, Markup) {
+    fn render_page(&self, sd: &SiteData, si: &SiteIndex<'_>) -> (PageMeta, Markup) {
+        let backlinks = si.backlinks(&self.post.meta().href());
         let meta = PageMeta {
             title: self.post.meta().title.clone(),
             href: "/blog".into(),
@@ -216,7 +224,7 @@ impl BaseTemplatePage for RenderPost<'_> {
         };
         let content = html! {
             main .container-md .longform {
-                (self.page_content(&sd.tags))
+                (self.page_content(&sd.tags, backlinks))
             }
         };
         (meta, content)
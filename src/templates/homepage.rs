@@ -1,5 +1,6 @@
 use itertools::Itertools;
 use maud::{html, Markup, PreEscaped};
+use tracing::warn;
 
 use crate::{
     model::{Button88x31, NewsItem, SiteData, SiteIndex},
@@ -8,6 +9,12 @@ use crate::{
 
 pub const MAX_POSTS_ON_FRONT_PAGE: usize = 5;
 
+const UNDER_CONSTRUCTION_GIF: &str = "https://s3.us-west-000.backblazeb2.com/nyaabucket/0aaa02e26cd9aee680f4ac3a2dc2f9c9e6792cdebcfc6d93255104e033de4654/under-construction.gif";
+const TOILET_JPG: &str = "https://s3.us-west-000.backblazeb2.com/nyaabucket/a2585655402f1d3476373477591269e89b37f8634a8c61cfde7c8f3e90d4dd72/toilet.jpg";
+
+/// Remote images that templates use, so they can be mirrored like the ones in content.
+pub const TEMPLATE_IMAGES: &[&str] = &[UNDER_CONSTRUCTION_GIF, TOILET_JPG];
+
 pub struct Homepage;
 
 impl BaseTemplatePage for Homepage {
@@ -22,7 +29,7 @@ impl BaseTemplatePage for Homepage {
                 div style="text-align: center" {
                     img
                         style="width: 100%; max-height: 64px"
-                        src="https://s3.us-west-000.backblazeb2.com/nyaabucket/0aaa02e26cd9aee680f4ac3a2dc2f9c9e6792cdebcfc6d93255104e033de4654/under-construction.gif"
+                        src=(sd.image_url(UNDER_CONSTRUCTION_GIF))
                         alt="under construction banner"
                         title="we are UNDER CONSTRUCTION!!!";
                 }
@@ -42,7 +49,7 @@ impl BaseTemplatePage for Homepage {
                     div .col .col-right {
                         (news_box(sd.news.iter().collect()))
                         cat-chatbox { }
-                        (swear_counter(si))
+                        (term_counter(sd, si))
                     }
                 }
                 (buttons(&sd.buttons))
@@ -126,13 +133,20 @@ fn buttons<'a>(buttons: impl IntoIterator<Item = &'a Button88x31>) -> Markup {
     }
 }
 
-fn swear_counter<'a>(si: &'a SiteIndex) -> Markup {
-    let total: usize = si.swear_count.values().sum();
-    let mut breakdown = si
-        .swear_count
-        .iter()
-        .filter(|(_, c)| **c != 0)
-        .collect_vec();
+fn term_counter(sd: &SiteData, si: &SiteIndex) -> Markup {
+    let Some(id) = &sd.stats.homepage_counter else {
+        return html! {};
+    };
+    let Some(counts) = si.term_counts.iter().find(|tc| &tc.counter.id == id) else {
+        warn!(
+            id,
+            "Homepage counter does not exist, so it will not be shown"
+        );
+        return html! {};
+    };
+
+    let total = counts.total();
+    let mut breakdown = counts.counts.iter().filter(|(_, c)| **c != 0).collect_vec();
 
     breakdown.sort_by_key(|(_, c)| **c);
     breakdown.reverse();
@@ -148,10 +162,10 @@ fn swear_counter<'a>(si: &'a SiteIndex) -> Markup {
                 width="64"
                 height="64"
                 alt="toilet"
-                src="https://s3.us-west-000.backblazeb2.com/nyaabucket/a2585655402f1d3476373477591269e89b37f8634a8c61cfde7c8f3e90d4dd72/toilet.jpg";
+                src=(sd.image_url(TOILET_JPG));
 
             p style="margin-top: 0px; margin-bottom: 8px" {
-                "Total swears: " (total.to_string())
+                a href="/stats" { "Total " (counts.counter.title.to_lowercase()) } ": " (total.to_string())
             }
         }
     }
@@ -5,6 +5,7 @@ mod computer;
 mod homepage;
 mod navbar;
 mod project;
+mod stats;
 mod tag_page;
 mod util;
 
@@ -14,4 +15,5 @@ pub use blog::*;
 pub use computer::*;
 pub use homepage::*;
 pub use project::*;
+pub use stats::*;
 pub use tag_page::*;
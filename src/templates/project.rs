@@ -4,9 +4,9 @@ use crate::{
     load::document::FullyLoadedDocument,
     model::{
         metadata::{Project, ProjectDates},
-        SiteData, SiteIndex, TagMap, TaggableExt,
+        Backlink, SiteData, SiteIndex, TagMap, TaggableExt,
     },
-    templates::util::tag_list,
+    templates::util::{backlinks_section, tag_list},
 };
 
 use super::{util::format_project_date, BaseTemplatePage, PageMeta};
@@ -82,7 +82,7 @@ impl<'a> RenderProject<'a> {
         }
     }
 
-    pub fn page_content(&self, tags: &TagMap) -> Markup {
+    pub fn page_content(&self, tags: &TagMap, backlinks: &[Backlink]) -> Markup {
         html! {
             article .project-content {
                 header {
@@ -109,6 +109,8 @@ impl<'a> RenderProject<'a> {
                 }
 
                 (PreEscaped(&self.project.html()))
+
+                (backlinks_section(backlinks))
             }
         }
     }
@@ -149,10 +151,11 @@ impl<'a> RenderProject<'a> {
 }
 
 impl BaseTemplatePage for RenderProject<'_> {
-    fn render_page(&self, sd: &SiteData, _si: &SiteIndex<'_>) -> (PageMeta, Markup) {
+    fn render_page(&self, sd: &SiteData, si: &SiteIndex<'_>) -> (PageMeta, Markup) {
+        let backlinks = si.backlinks(&self.project.meta().href());
         let content = html! {
             main .container-md .longform {
-                (self.page_content(&sd.tags))
+                (self.page_content(&sd.tags, backlinks))
             }
         };
 
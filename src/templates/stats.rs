@@ -0,0 +1,207 @@
+use chrono::Month;
+use itertools::Itertools;
+use maud::{html, Markup, Render};
+
+use crate::{
+    model::{SiteData, SiteIndex, TermCounts},
+    transform::statistics::DocumentStatistics,
+};
+
+use super::{util::TagR, BaseTemplatePage, PageMeta, RenderPost};
+
+pub const MAX_LONGEST_POSTS: usize = 5;
+pub const MAX_TOP_TAGS: usize = 10;
+
+#[derive(Clone)]
+pub struct StatsPage;
+
+impl BaseTemplatePage for StatsPage {
+    fn render_page(&self, sd: &SiteData, si: &SiteIndex) -> (PageMeta, Markup) {
+        let total = si.post_stats + si.project_stats + si.page_stats;
+
+        let mut longest = sd.posts.iter().collect_vec();
+        longest.sort_by_key(|p| p.stats().words);
+        longest.reverse();
+        longest.truncate(MAX_LONGEST_POSTS);
+
+        let content = html! {
+            main .container-md .longform {
+                h1 style="text-align: center;" { "Site statistics" }
+
+                section {
+                    h2 { "Content" }
+                    table .stats-table style="width: 100%" {
+                        thead {
+                            th { }
+                            th { "Documents" }
+                            th { "Words" }
+                            th { "Reading time" }
+                            th { "Code blocks" }
+                            th { "Images" }
+                            th { "Math blocks" }
+                            th { "Videos" }
+                            th { "Audio" }
+                        }
+                        tbody {
+                            (StatsRow { label: "Posts", count: sd.posts.len(), stats: &si.post_stats })
+                            (StatsRow { label: "Projects", count: sd.projects.len(), stats: &si.project_stats })
+                            (StatsRow { label: "Pages", count: sd.pages.len(), stats: &si.page_stats })
+                            (StatsRow {
+                                label: "Total",
+                                count: sd.posts.len() + sd.projects.len() + sd.pages.len(),
+                                stats: &total,
+                            })
+                        }
+                    }
+                }
+
+                section {
+                    h2 { "Longest posts" }
+                    ol {
+                        @for p in longest {
+                            li {
+                                (RenderPost::from(p).linked_title())
+                                " (" (p.stats().words) " words)"
+                            }
+                        }
+                    }
+                }
+
+                section {
+                    h2 { "Posts over time" }
+                    (posts_per_month(si))
+                }
+
+                section {
+                    h2 { "Top tags" }
+                    ol {
+                        @for (t, c) in si.tag_counts.iter().take(MAX_TOP_TAGS) {
+                            li { (TagR::new(&sd.tags[*t])) " " (c) }
+                        }
+                    }
+                }
+
+                @for tc in &si.term_counts {
+                    (term_counts(tc))
+                }
+
+                section {
+                    h2 { "Computers" }
+                    p { "Total computers: " (sd.computers.len()) }
+                    table .stats-table {
+                        tbody {
+                            @for (status, c) in &si.computer_stats.by_status {
+                                tr { th { (status) } td { (c) } }
+                            }
+                        }
+                    }
+                    table .stats-table {
+                        tbody {
+                            @for (ty, c) in &si.computer_stats.by_type {
+                                tr { th { (ty) } td { (c) } }
+                            }
+                        }
+                    }
+                }
+            }
+        };
+
+        let meta = PageMeta {
+            title: "Statistics".into(),
+            href: "/stats".into(),
+            extra_head: html! {
+                meta property="og:title" content="Statistics";
+                meta property="og:description" content="Statistics about this site";
+                meta property="og:url" content="https://astrid.tech/stats";
+                meta property="og:type" content="website";
+            },
+            ..Default::default()
+        };
+
+        (meta, content)
+    }
+}
+
+struct StatsRow<'a> {
+    label: &'a str,
+    count: usize,
+    stats: &'a DocumentStatistics,
+}
+
+impl Render for StatsRow<'_> {
+    fn render(&self) -> Markup {
+        let hours = self.stats.reading_time().as_secs() as f64 / 3600.0;
+
+        html! {
+            tr {
+                th { (self.label) }
+                td { (self.count) }
+                td { (self.stats.words) }
+                td { (format!("{hours:.1} hours")) }
+                td { (self.stats.code_blocks) }
+                td { (self.stats.images) }
+                td { (self.stats.math_blocks) }
+                td { (self.stats.videos) }
+                td { (self.stats.audio) }
+            }
+        }
+    }
+}
+
+fn posts_per_month(si: &SiteIndex) -> Markup {
+    let years = si
+        .posts_per_month
+        .keys()
+        .map(|(y, _)| *y)
+        .dedup()
+        .collect_vec();
+
+    html! {
+        table .stats-table style="width: 100%" {
+            thead {
+                th { "Year" }
+                @for m in 1..=12u8 {
+                    th { (&Month::try_from(m).unwrap().name()[..3]) }
+                }
+                th { "Total" }
+            }
+            tbody {
+                @for y in years.into_iter().rev() {
+                    tr {
+                        th { (y) }
+                        @for m in 1..=12 {
+                            td {
+                                @if let Some(c) = si.posts_per_month.get(&(y, m)) {
+                                    (c)
+                                }
+                            }
+                        }
+                        td {
+                            (si.posts_per_month.range((y, 1)..=(y, 12)).map(|(_, c)| c).sum::<usize>())
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn term_counts(tc: &TermCounts) -> Markup {
+    let mut breakdown = tc.counts.iter().filter(|(_, c)| **c != 0).collect_vec();
+    breakdown.sort_by_key(|(_, c)| **c);
+    breakdown.reverse();
+
+    html! {
+        section {
+            h2 { (tc.counter.title) }
+            p { "Total: " (tc.total()) }
+            table .stats-table {
+                tbody {
+                    @for (term, c) in breakdown {
+                        tr { th { (term) } td { (c) } }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,7 +1,10 @@
 use chrono::{Datelike, Month, Timelike};
 use maud::{html, Markup, PreEscaped, Render};
 
-use crate::model::{TagMap, TagSettings, TagStyling, Webring};
+use crate::{
+    model::{Backlink, TagMap, TagSettings, TagStyling, Webring},
+    transform::statistics::DocumentStatistics,
+};
 
 pub struct EmDash;
 
@@ -34,6 +37,10 @@ pub fn format_dt_html(d: impl Datelike + Timelike) -> Markup {
     }
 }
 
+pub fn format_reading_time(stats: &DocumentStatistics) -> String {
+    format!("{} min read", stats.reading_minutes())
+}
+
 pub fn tag_list<I, S>(tag_map: &TagMap, tags: I) -> Markup
 where
     I: IntoIterator<Item = S>,
@@ -99,6 +106,21 @@ impl Render for TagR<'_> {
     }
 }
 
+pub fn backlinks_section(backlinks: &[Backlink]) -> Markup {
+    html! {
+        @if !backlinks.is_empty() {
+            section .backlinks {
+                h2 { "Pages that link here" }
+                ul {
+                    @for b in backlinks {
+                        li { a href=(b.href) { (b.title) } }
+                    }
+                }
+            }
+        }
+    }
+}
+
 #[derive(derive_more::From)]
 pub struct RenderWebring<'a> {
     webring: &'a Webring,
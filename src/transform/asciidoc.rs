@@ -0,0 +1,686 @@
+//! AsciiDoc support, by translating a commonly used subset of it into
+//! markdown so that it goes through the same transforms as everything else.
+//!
+//! Like Org, lines are translated one-to-one wherever possible, so that
+//! positions in errors still point at the right place in the original file.
+
+use crate::errors::Errors;
+
+use super::{
+    common::{TransformContext, TransformedContent},
+    markdown::{transform_markdown, MarkdownError},
+    markup::{
+        code_span, display_math, escape_math, is_bare_relative, push_escaped, table_separator,
+    },
+};
+
+/// Transform AsciiDoc into HTML, the same way as markdown.
+#[tracing::instrument(skip_all)]
+pub async fn transform_asciidoc(
+    ctx: &TransformContext<'_>,
+    raw: &str,
+) -> Result<TransformedContent, Errors<MarkdownError>> {
+    transform_markdown(ctx, &asciidoc_to_markdown(raw)).await
+}
+
+/// What kind of delimited block the translator is inside of.
+enum Block {
+    Normal,
+    /// A listing or literal block, copied verbatim until the given delimiter.
+    Verbatim(String),
+    /// A passthrough block of raw HTML.
+    Passthrough,
+    /// A passthrough block of display math.
+    Math,
+    Quote,
+    Comment,
+    /// A table, with the lines it has taken up so far.
+    Table(Vec<String>),
+}
+
+/// Admonition labels, and how they're shown.
+const ADMONITIONS: [(&str, &str); 5] = [
+    ("NOTE: ", "Note"),
+    ("TIP: ", "Tip"),
+    ("IMPORTANT: ", "Important"),
+    ("WARNING: ", "Warning"),
+    ("CAUTION: ", "Caution"),
+];
+
+/// Translate an AsciiDoc document into equivalent markdown.
+pub fn asciidoc_to_markdown(adoc: &str) -> String {
+    Translator::default().document(adoc)
+}
+
+#[derive(Default)]
+struct Translator {
+    /// The `:imagesdir:` attribute, which image targets are relative to.
+    images_dir: Option<String>,
+    /// Footnote definitions, to go at the end of the document.
+    footnotes: Vec<String>,
+}
+
+impl Translator {
+    fn document(mut self, adoc: &str) -> String {
+        let mut out: Vec<String> = vec![];
+        let mut block = Block::Normal;
+        let mut math: Vec<&str> = vec![];
+        // The block attribute line, like `[source,rust]`, for the next block
+        let mut attributes: Option<String> = None;
+
+        for line in adoc.lines() {
+            let trimmed = line.trim();
+
+            match &mut block {
+                Block::Verbatim(end) => {
+                    if trimmed == end.as_str() {
+                        out.push("```".into());
+                        block = Block::Normal;
+                    } else {
+                        out.push(line.into());
+                    }
+                    continue;
+                }
+                Block::Passthrough => {
+                    if trimmed == "++++" {
+                        out.push(String::new());
+                        block = Block::Normal;
+                    } else {
+                        out.push(line.into());
+                    }
+                    continue;
+                }
+                Block::Math => {
+                    if trimmed == "++++" {
+                        if !math.is_empty() {
+                            out.extend(display_math(&math));
+                        }
+                        out.push(String::new());
+                        math.clear();
+                        block = Block::Normal;
+                    } else {
+                        math.push(line);
+                    }
+                    continue;
+                }
+                Block::Comment => {
+                    if trimmed == "////" {
+                        block = Block::Normal;
+                    }
+                    out.push(String::new());
+                    continue;
+                }
+                Block::Table(lines) => {
+                    if trimmed == "|===" {
+                        lines.push(String::new());
+                        out.extend(table(lines));
+                        block = Block::Normal;
+                    } else if trimmed.starts_with('|') {
+                        lines.push(self.table_row(trimmed));
+                    } else {
+                        lines.push(String::new());
+                    }
+                    continue;
+                }
+                Block::Normal | Block::Quote => (),
+            }
+
+            let style = attributes.take();
+            let style = style.as_deref().unwrap_or_default();
+            let style_name = style.split(',').next().unwrap_or_default().trim();
+
+            let translated = if is_delimiter(trimmed, '-') {
+                let lang = match style_name {
+                    "source" => style.split(',').nth(1).unwrap_or_default().trim(),
+                    "graphviz" => "dot",
+                    _ => "",
+                };
+                block = Block::Verbatim(trimmed.into());
+                format!("```{lang}")
+            } else if is_delimiter(trimmed, '.') {
+                block = Block::Verbatim(trimmed.into());
+                "```".into()
+            } else if trimmed == "++++" {
+                block = match style_name {
+                    "stem" | "latexmath" => Block::Math,
+                    _ => Block::Passthrough,
+                };
+                String::new()
+            } else if trimmed == "____" {
+                block = match block {
+                    Block::Quote => Block::Normal,
+                    _ => Block::Quote,
+                };
+                String::new()
+            } else if trimmed == "////" {
+                block = Block::Comment;
+                String::new()
+            } else if trimmed == "|===" {
+                block = Block::Table(vec![]);
+                continue;
+            } else if is_delimiter(trimmed, '=') || is_delimiter(trimmed, '*') {
+                // Example blocks and sidebars, which are just shown as they are
+                String::new()
+            } else if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                // Block attributes and anchors
+                attributes = Some(trimmed[1..trimmed.len() - 1].into());
+                String::new()
+            } else if let Some(dir) = attribute_entry(trimmed, "imagesdir") {
+                self.images_dir = (!dir.is_empty()).then(|| dir.into());
+                String::new()
+            } else if trimmed.starts_with("//") || attribute_entry(trimmed, "").is_some() {
+                // Comments and document attributes
+                String::new()
+            } else {
+                self.line(line)
+            };
+
+            out.push(match block {
+                Block::Quote if !translated.is_empty() => format!("> {translated}"),
+                _ => translated,
+            });
+        }
+
+        // Close anything left open, instead of losing it
+        match block {
+            Block::Verbatim(_) => out.push("```".into()),
+            Block::Math => out.extend(display_math(&math)),
+            Block::Table(lines) => out.extend(table(&lines)),
+            Block::Normal | Block::Passthrough | Block::Quote | Block::Comment => (),
+        }
+
+        if !self.footnotes.is_empty() {
+            out.push(String::new());
+            for (i, note) in self.footnotes.iter().enumerate() {
+                out.push(format!("[^{}]: {note}", i + 1));
+            }
+        }
+
+        out.join("\n") + "\n"
+    }
+
+    /// Translate a line outside of any verbatim block.
+    fn line(&mut self, line: &str) -> String {
+        let trimmed = line.trim();
+
+        // Hard line breaks
+        if let Some(rest) = trimmed.strip_suffix(" +") {
+            return format!("{}\\", self.line(rest));
+        }
+
+        // Section titles, where `==` is the first level. The document title
+        // is left out, like Org's `#+TITLE`.
+        let level = trimmed.len() - trimmed.trim_start_matches('=').len();
+        if level > 0 && trimmed[level..].starts_with(' ') {
+            return match level {
+                1 => String::new(),
+                _ => format!(
+                    "{} {}",
+                    "#".repeat((level - 1).min(6)),
+                    self.inline(trimmed[level..].trim())
+                ),
+            };
+        }
+
+        // Thematic and page breaks
+        if trimmed == "'''" {
+            return "---".into();
+        }
+        if trimmed == "<<<" {
+            return String::new();
+        }
+
+        // Block images
+        if let Some(rest) = trimmed.strip_prefix("image::") {
+            if let Some((target, attrs)) = macro_parts(rest) {
+                return self.image(target, attrs);
+            }
+        }
+
+        // Block titles
+        if let Some(title) = trimmed.strip_prefix('.') {
+            if title.starts_with(|c: char| !c.is_whitespace() && c != '.') {
+                return format!("*{}*", self.inline(title));
+            }
+        }
+
+        for (label, name) in ADMONITIONS {
+            if let Some(text) = trimmed.strip_prefix(label) {
+                return format!("> **{name}:** {}", self.inline(text));
+            }
+        }
+
+        // List items
+        for (bullet, marker, width) in [('*', "-", 2), ('-', "-", 2), ('.', "1.", 3)] {
+            let depth = trimmed.len() - trimmed.trim_start_matches(bullet).len();
+            if depth > 0 && (bullet != '-' || depth == 1) {
+                if let Some(item) = trimmed[depth..].strip_prefix(' ') {
+                    let indent = " ".repeat((depth - 1) * width);
+                    return format!("{indent}{marker} {}", self.inline(item.trim()));
+                }
+            }
+        }
+        if let Some((term, desc)) = trimmed
+            .split_once(":: ")
+            .or_else(|| Some((trimmed.strip_suffix("::")?, "")))
+        {
+            return format!("**{}**: {}", self.inline(term), self.inline(desc.trim()));
+        }
+
+        self.inline(trimmed)
+    }
+
+    fn table_row(&mut self, row: &str) -> String {
+        let cells = row
+            .split('|')
+            .skip(1)
+            .map(|c| self.inline(c.trim()))
+            .collect::<Vec<_>>();
+        format!("| {} |", cells.join(" | "))
+    }
+
+    /// An image macro's target and attributes as a markdown image.
+    fn image(&mut self, target: &str, attrs: &str) -> String {
+        let alt = attrs.split(',').next().unwrap_or_default().trim();
+        let target = match &self.images_dir {
+            Some(dir) if is_bare_relative(target) => {
+                format!("{}/{target}", dir.trim_end_matches('/'))
+            }
+            _ => target.into(),
+        };
+        format!("![{}]({})", escape_link_text(alt), link_target(&target))
+    }
+
+    /// Translate inline markup, like formatting, macros and math.
+    fn inline(&mut self, text: &str) -> String {
+        let chars = text.chars().collect::<Vec<_>>();
+        let mut out = String::new();
+        let mut i = 0;
+
+        let at = |i: usize, pat: &str| chars[i..].iter().copied().take(pat.len()).eq(pat.chars());
+        let find = |from: usize, pat: &str| (from..chars.len()).find(|&j| at(j, pat));
+        let text_of = |from: usize, to: usize| chars[from..to].iter().collect::<String>();
+
+        while i < chars.len() {
+            let c = chars[i];
+            let prev = if i == 0 { None } else { Some(chars[i - 1]) };
+            let word_start = !prev.is_some_and(|p| p.is_alphanumeric());
+
+            // Escaped syntax
+            if c == '\\' && chars.get(i + 1).is_some_and(|n| n.is_ascii_punctuation()) {
+                push_escaped(&mut out, chars[i + 1]);
+                i += 2;
+                continue;
+            }
+
+            // Passthroughs, which are raw HTML with three pluses and plain
+            // text otherwise
+            if let Some(pluses) = ["+++", "++", "+"].into_iter().find(|p| at(i, p)) {
+                if let Some(end) = find(i + pluses.len() + 1, pluses) {
+                    let body = text_of(i + pluses.len(), end);
+                    if pluses == "+++" {
+                        out.push_str(&body);
+                    } else {
+                        body.chars().for_each(|c| push_escaped(&mut out, c));
+                    }
+                    i = end + pluses.len();
+                    continue;
+                }
+            }
+
+            // Macros, like `stem:[x]` and `link:url[text]`
+            if word_start && c.is_ascii_alphabetic() {
+                if let Some((name, target, attrs, end)) = inline_macro(&chars, i) {
+                    let translated = match name.as_str() {
+                        "stem" | "latexmath" if target.is_empty() => {
+                            Some(format!("<m>{}</m>", escape_math(&attrs)))
+                        }
+                        "pass" if target.is_empty() => Some(attrs),
+                        "footnote" => {
+                            let note = self.inline(&attrs);
+                            self.footnotes.push(note);
+                            Some(format!("[^{}]", self.footnotes.len()))
+                        }
+                        "image" => Some(self.image(&target, &attrs)),
+                        "link" | "xref" | "mailto" | "http" | "https" => {
+                            let url = match name.as_str() {
+                                "link" | "xref" => target,
+                                _ => format!("{name}:{target}"),
+                            };
+                            let text = attrs.split(',').next().unwrap_or_default().trim();
+                            let text = match text {
+                                "" => escape_link_text(&url),
+                                t => self.inline(t),
+                            };
+                            Some(format!("[{text}]({})", link_target(&url)))
+                        }
+                        _ => None,
+                    };
+                    if let Some(t) = translated {
+                        out.push_str(&t);
+                        i = end;
+                        continue;
+                    }
+                }
+
+                // Bare URLs
+                if at(i, "https://") || at(i, "http://") {
+                    let end = (i..chars.len())
+                        .find(|&j| chars[j].is_whitespace() || chars[j] == '[')
+                        .unwrap_or(chars.len());
+                    let url = text_of(i, end);
+                    let url = url.trim_end_matches(['.', ',', ';', ':', '!', '?', ')']);
+                    out.push_str(&format!("<{url}>"));
+                    i += url.chars().count();
+                    continue;
+                }
+            }
+
+            // Cross references
+            if at(i, "<<") {
+                if let Some(end) = find(i + 2, ">>") {
+                    let inner = text_of(i + 2, end);
+                    let (id, text) = inner.split_once(',').unwrap_or((&inner, &inner));
+                    out.push_str(&format!("[{}](#{})", self.inline(text.trim()), id.trim()));
+                    i = end + 2;
+                    continue;
+                }
+            }
+
+            // Code
+            if c == '`' {
+                if let Some(end) = find(i + 1, "`") {
+                    let code = text_of(i + 1, end);
+                    let code = code
+                        .strip_prefix('+')
+                        .and_then(|c| c.strip_suffix('+'))
+                        .unwrap_or(&code);
+                    out.push_str(&code_span(code));
+                    i = end + 1;
+                    continue;
+                }
+            }
+
+            // Formatting, either unconstrained and doubled, or constrained to
+            // whole words
+            if let Some((open, close)) = formatting(c) {
+                let doubled = chars.get(i + 1) == Some(&c);
+                let marker = if doubled { 2 } else { 1 };
+                let end = if doubled {
+                    find(i + 3, &format!("{c}{c}"))
+                } else if word_start && chars.get(i + 1).is_some_and(|n| !n.is_whitespace()) {
+                    (i + 2..chars.len()).find(|&j| {
+                        chars[j] == c
+                            && !chars[j - 1].is_whitespace()
+                            && !chars.get(j + 1).is_some_and(|n| n.is_alphanumeric())
+                    })
+                } else {
+                    None
+                };
+                if let Some(end) = end {
+                    let body = text_of(i + marker, end);
+                    out.push_str(&format!("{open}{}{close}", self.inline(&body)));
+                    i = end + marker;
+                    continue;
+                }
+            }
+
+            // Superscripts and subscripts, which can't contain spaces
+            if let Some(tag) = match c {
+                '^' => Some("sup"),
+                '~' => Some("sub"),
+                _ => None,
+            } {
+                let end = (i + 2..chars.len())
+                    .take_while(|&j| !chars[j].is_whitespace())
+                    .find(|&j| chars[j] == c);
+                if let Some(end) = end {
+                    let body = self.inline(&text_of(i + 1, end));
+                    out.push_str(&format!("<{tag}>{body}</{tag}>"));
+                    i = end + 1;
+                    continue;
+                }
+            }
+
+            // Anything else that markdown would treat as syntax
+            push_escaped(&mut out, c);
+            i += 1;
+        }
+
+        out
+    }
+}
+
+/// Whether the line is a block delimiter, made of four or more of `c`.
+fn is_delimiter(line: &str, c: char) -> bool {
+    line.len() >= 4 && line.chars().all(|x| x == c)
+}
+
+/// The value of an attribute entry like `:name: value`, or of any attribute
+/// entry if `name` is empty.
+fn attribute_entry<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let (entry, value) = line.strip_prefix(':')?.split_once(':')?;
+    let matches = if name.is_empty() {
+        !entry.is_empty() && !entry.contains(char::is_whitespace)
+    } else {
+        entry == name
+    };
+    matches
+        .then_some(value)
+        .filter(|v| v.is_empty() || v.starts_with(' '))
+        .map(str::trim)
+}
+
+/// Fit a table's rows into the lines it took up, with the first row as the
+/// header. Markdown tables can't have blank lines in them, so any are moved
+/// to the end.
+fn table(lines: &[String]) -> Vec<String> {
+    let mut out = vec![];
+    let mut rows_iter = lines.iter().filter(|l| !l.is_empty());
+    if let Some(header) = rows_iter.next() {
+        let columns = header.matches(" | ").count() + 1;
+        out.push(header.clone());
+        out.push(table_separator(columns));
+        out.extend(rows_iter.cloned());
+    }
+    // The opening delimiter isn't in `lines`
+    out.resize(lines.len() + 1, String::new());
+    out
+}
+
+/// Formatting marks, and what they become in markdown.
+fn formatting(marker: char) -> Option<(&'static str, &'static str)> {
+    Some(match marker {
+        '*' => ("**", "**"),
+        '_' => ("*", "*"),
+        '#' => ("<mark>", "</mark>"),
+        _ => return None,
+    })
+}
+
+/// Split a block macro like `cat.png[A cat]` into its target and attributes.
+fn macro_parts(rest: &str) -> Option<(&str, &str)> {
+    let (target, attrs) = rest.split_once('[')?;
+    Some((target, attrs.strip_suffix(']')?))
+}
+
+/// Parse an inline macro like `name:target[attributes]` starting at `i`,
+/// returning its parts and where it ends.
+fn inline_macro(chars: &[char], i: usize) -> Option<(String, String, String, usize)> {
+    let colon = (i..chars.len())
+        .take_while(|&j| chars[j].is_ascii_alphanumeric() || chars[j] == ':')
+        .find(|&j| chars[j] == ':')?;
+    let open = (colon + 1..chars.len())
+        .take_while(|&j| !chars[j].is_whitespace())
+        .find(|&j| chars[j] == '[')?;
+    let close = closing_bracket(chars, open)?;
+
+    let text_of = |from: usize, to: usize| chars[from..to].iter().collect::<String>();
+    Some((
+        text_of(i, colon),
+        text_of(colon + 1, open),
+        text_of(open + 1, close).replace("\\]", "]"),
+        close + 1,
+    ))
+}
+
+/// The index of the `]` matching the `[` at `open`, skipping escaped ones.
+fn closing_bracket(chars: &[char], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (j, &c) in chars.iter().enumerate().skip(open) {
+        match c {
+            ']' if chars[j - 1] == '\\' => continue,
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(j);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Turn a link or image target into a URL. Relative files are made
+/// explicitly relative, so that local images get relinked.
+fn link_target(target: &str) -> String {
+    let target = target.trim().replace(' ', "%20");
+    if is_bare_relative(&target) {
+        format!("./{target}")
+    } else {
+        target
+    }
+}
+
+fn escape_link_text(text: &str) -> String {
+    text.replace('[', "\\[").replace(']', "\\]")
+}
+
+#[cfg(test)]
+mod tests {
+    use vfs::{MemoryFS, VfsPath};
+
+    use crate::{media::MediaRegistry, transform::links::LinkTargets};
+
+    use super::*;
+
+    #[test]
+    pub fn translates_asciidoc_to_markdown() {
+        let adoc = r#"= Notes
+:toc:
+:imagesdir: img
+
+== Intro
+Some *bold*, _italic_, **b**old, #marked#, `x*y` and +*plain*+. 2 * 3 = 6.
+See https://example.com[the _site_], <<math,below>> and image:cat.png[A cat].footnote:[A footnote.]
+// A comment
+
+[[math]]
+=== Math
+Inline stem:[e^{i\pi}] and E=mc^2^ and H~2~O.
+[stem]
+++++
+x = \frac{1}{2}
+++++
+
+[source,rust]
+----
+fn main() {}
+* not a list
+----
+
+* item one
+** nested
+. first
+term:: meaning
+NOTE: Careful.
+One +
+two
+
+.A table
+|===
+| a | b
+
+| 1 | x_y
+|===
+"#;
+
+        assert_eq!(
+            asciidoc_to_markdown(adoc),
+            r#"
+
+
+
+# Intro
+Some **bold**, *italic*, **b**old, <mark>marked</mark>, `x*y` and \*plain\*. 2 \* 3 = 6.
+See [the *site*](https://example.com), [below](#math) and ![A cat](./img/cat.png).[^1]
+
+
+
+## Math
+Inline <m>e\^\{i\\pi\}</m> and E=mc<sup>2</sup> and H<sub>2</sub>O.
+
+
+<M>x \= \\frac\{1\}\{2\}</M>
+
+
+
+```rust
+fn main() {}
+* not a list
+```
+
+- item one
+  - nested
+1. first
+**term**: meaning
+> **Note:** Careful.
+One\
+two
+
+*A table*
+| a | b |
+|---|---|
+| 1 | x\_y |
+
+
+
+[^1]: A footnote.
+"#
+        );
+    }
+
+    #[test]
+    pub fn translates_graphviz_and_literal_blocks() {
+        let adoc = "[graphviz]\n----\ndigraph { a -> b }\n----\n....\n*literal*\n....\n";
+
+        assert_eq!(
+            asciidoc_to_markdown(adoc),
+            "\n```dot\ndigraph { a -> b }\n```\n```\n*literal*\n```\n"
+        );
+    }
+
+    #[tokio::test]
+    pub async fn relinks_images_like_markdown() {
+        let root = VfsPath::new(MemoryFS::new());
+        root.join("cat.png")
+            .unwrap()
+            .create_file()
+            .unwrap()
+            .write_all(b"meow")
+            .unwrap();
+        let media = MediaRegistry::new("/static".into(), VfsPath::new(MemoryFS::new()));
+        let links = LinkTargets::default();
+        let ctx = TransformContext::new(root, &media, &links);
+
+        let out = transform_asciidoc(&ctx, "== Cat\nimage::cat.png[A cat]\n")
+            .await
+            .unwrap();
+
+        assert!(out.html.contains("<h1>"), "html = {}", out.html);
+        assert!(out.html.contains("/static/"), "html = {}", out.html);
+        assert_eq!(out.stats.images, 1);
+    }
+}
@@ -2,16 +2,30 @@ use vfs::VfsPath;
 
 use crate::media::MediaRegistry;
 
+use super::{links::LinkTargets, statistics::DocumentStatistics};
+
+/// Content that has been transformed into HTML.
+#[derive(Clone)]
+pub struct TransformedContent {
+    /// The raw HTML.
+    pub html: String,
+
+    /// Statistics about the content, like word count.
+    pub stats: DocumentStatistics,
+}
+
 pub struct TransformContext<'a> {
     content_root: VfsPath,
     media: &'a MediaRegistry,
+    links: &'a LinkTargets,
 }
 
 impl<'a> TransformContext<'a> {
-    pub fn new(content_root: VfsPath, media: &'a MediaRegistry) -> Self {
+    pub fn new(content_root: VfsPath, media: &'a MediaRegistry, links: &'a LinkTargets) -> Self {
         Self {
             content_root,
             media,
+            links,
         }
     }
 
@@ -22,4 +36,8 @@ impl<'a> TransformContext<'a> {
     pub fn media(&self) -> &MediaRegistry {
         &self.media
     }
+
+    pub fn links(&self) -> &LinkTargets {
+        self.links
+    }
 }
@@ -0,0 +1,311 @@
+use std::io::Cursor;
+
+use comrak::nodes::{AstNode, NodeHtmlBlock, NodeValue};
+use image::{imageops::FilterType, ImageFormat};
+use maud::{html, Markup};
+use vfs::{VfsError, VfsPath};
+
+use crate::{
+    errors::Errors,
+    media::{Media, Uploadable},
+};
+
+use super::{
+    common::TransformContext,
+    markdown::{is_local_image, MarkdownError},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum GalleryError {
+    #[error("gallery images must be local, like ./photo.jpg, but got {0:?}")]
+    NotLocal(String),
+
+    #[error("invalid glob {0:?}: {1}")]
+    Pattern(String, glob::PatternError),
+
+    #[error("no images match {0:?}")]
+    NoMatches(String),
+
+    #[error("fs error: {0}")]
+    Vfs(#[from] VfsError),
+
+    #[error("could not upload {0:?}: {1}")]
+    Upload(String, anyhow::Error),
+}
+
+/// Size of the square thumbnails in the grid, in pixels. This is twice the
+/// width of a grid column, so they stay sharp on high-DPI screens.
+pub const THUMBNAIL_SIZE: u32 = 360;
+
+/// One image in a gallery.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GalleryImage {
+    pub url: String,
+
+    /// URL of a smaller version of the image, to show in the grid.
+    pub thumbnail: String,
+
+    pub caption: Option<String>,
+}
+
+/// Shrink an image into a square thumbnail, cropping it to fit.
+///
+/// Returns [None] if the image is already small enough, or can't be decoded
+/// (like SVGs), in which case the original can be used instead.
+pub fn make_thumbnail(media: &Media) -> Option<Media> {
+    let format = image::guess_format(&media.body).ok()?;
+    let image = image::load_from_memory_with_format(&media.body, format).ok()?;
+    if image.width() <= THUMBNAIL_SIZE && image.height() <= THUMBNAIL_SIZE {
+        return None;
+    }
+
+    // Photos are smaller as JPEGs, but anything else might need transparency
+    let (format, mimetype, ext) = match format {
+        ImageFormat::Jpeg | ImageFormat::WebP => (ImageFormat::Jpeg, mime::IMAGE_JPEG, "jpg"),
+        _ => (ImageFormat::Png, mime::IMAGE_PNG, "png"),
+    };
+    let thumbnail = image.resize_to_fill(THUMBNAIL_SIZE, THUMBNAIL_SIZE, FilterType::Triangle);
+    let thumbnail = match format {
+        ImageFormat::Jpeg => image::DynamicImage::ImageRgb8(thumbnail.to_rgb8()),
+        _ => thumbnail,
+    };
+
+    let mut body = vec![];
+    thumbnail
+        .write_to(&mut Cursor::new(&mut body), format)
+        .ok()?;
+    let stem = match media.filename.as_deref() {
+        Some(f) => f.rsplit_once('.').map_or(f, |(s, _)| s),
+        None => "image",
+    };
+
+    Some(Media {
+        filename: Some(format!("{stem}.thumb.{ext}")),
+        mimetype: Some(mimetype),
+        body,
+    })
+}
+
+/// Parse the body of a `gallery` code fence into files and their captions.
+///
+/// Every line is a path relative to the document, optionally followed by a
+/// caption, like `./board.jpg The finished board`. Paths may be globs, in
+/// which case every matching file gets the caption.
+pub fn expand_gallery(
+    root: &VfsPath,
+    spec: &str,
+) -> Result<Vec<(VfsPath, Option<String>)>, GalleryError> {
+    let mut images = vec![];
+
+    for line in spec.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let (path, caption) = match line.split_once(char::is_whitespace) {
+            Some((p, c)) => (p, Some(c.trim().to_owned())),
+            None => (line, None),
+        };
+        if !is_local_image(path) {
+            return Err(GalleryError::NotLocal(path.into()));
+        }
+
+        if !path.contains(['*', '?', '[']) {
+            images.push((root.join(path)?, caption));
+            continue;
+        }
+
+        let pattern = glob::Pattern::new(path.trim_start_matches("./"))
+            .map_err(|e| GalleryError::Pattern(path.into(), e))?;
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        let mut matches = vec![];
+        for p in root.walk_dir()? {
+            let p = p?;
+            let relative = p.as_str()[root.as_str().len()..].trim_start_matches('/');
+            if pattern.matches_with(relative, options) && p.is_file()? {
+                matches.push(p);
+            }
+        }
+        if matches.is_empty() {
+            return Err(GalleryError::NoMatches(path.into()));
+        }
+        matches.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        images.extend(matches.into_iter().map(|p| (p, caption.clone())));
+    }
+
+    Ok(images)
+}
+
+/// A grid of thumbnails. Clicking on one opens the full image in the
+/// lightbox, if the script is loaded, and on its own otherwise.
+pub fn render_gallery(id: usize, images: &[GalleryImage]) -> Markup {
+    html! {
+        div.gallery data-gallery=(id) {
+            @for i in images {
+                figure.gallery-item {
+                    a href=(i.url) data-lightbox=(id) data-caption=[&i.caption] {
+                        img src=(i.thumbnail) alt=(i.caption.as_deref().unwrap_or_default()) loading="lazy";
+                    }
+                    @if let Some(c) = &i.caption {
+                        figcaption { (c) }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Replace `gallery` code fences with image grids, uploading their images.
+#[tracing::instrument(skip_all)]
+pub fn apply_galleries<'a>(
+    ctx: &TransformContext<'_>,
+    root: &'a AstNode<'a>,
+) -> Result<(), Errors<MarkdownError>> {
+    let mut errors = Errors::new();
+    let mut id = 0;
+
+    for n in root.descendants() {
+        let mut ast = n.data.borrow_mut();
+        let spec = match &ast.value {
+            NodeValue::CodeBlock(cb) if cb.info.trim() == "gallery" => cb.literal.clone(),
+            _ => continue,
+        };
+
+        let result = expand_gallery(ctx.content_root(), &spec).and_then(|files| {
+            files
+                .into_iter()
+                .map(|(f, caption)| {
+                    let upload = |media: Media| {
+                        ctx.media()
+                            .upload_media(media)
+                            .map_err(|e| GalleryError::Upload(f.filename(), e))
+                    };
+                    let media = f
+                        .as_media()
+                        .map_err(|e| GalleryError::Upload(f.filename(), e))?;
+                    let thumbnail = make_thumbnail(&media).map(upload).transpose()?;
+                    let url = upload(media)?;
+                    Ok(GalleryImage {
+                        thumbnail: thumbnail.unwrap_or_else(|| url.clone()),
+                        url,
+                        caption,
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        });
+
+        match result {
+            Ok(images) => {
+                ast.value = NodeValue::HtmlBlock(NodeHtmlBlock {
+                    block_type: 0,
+                    literal: render_gallery(id, &images).into_string(),
+                });
+                id += 1;
+            }
+            Err(e) => errors.push(MarkdownError::new(ast.sourcepos, e.into())),
+        }
+    }
+
+    errors.into_result()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use comrak::{format_html, parse_document, Arena};
+    use vfs::MemoryFS;
+
+    use crate::{
+        media::{content_hash, MediaRegistry},
+        transform::{links::LinkTargets, markdown::make_md_options},
+    };
+
+    use super::*;
+
+    #[test]
+    pub fn expands_lists_and_globs() {
+        let root = VfsPath::new(MemoryFS::new());
+        for name in ["build/2.jpg", "build/1.jpg", "build/sub/3.jpg", "final.jpg"] {
+            let p = root.join(name).unwrap();
+            p.parent().create_dir_all().unwrap();
+            p.create_file().unwrap();
+        }
+
+        let spec = "./final.jpg The finished board\n\n./build/*.jpg  Work in progress\n";
+        let images = expand_gallery(&root, spec)
+            .unwrap()
+            .into_iter()
+            .map(|(p, c)| (p.as_str().to_owned(), c))
+            .collect::<Vec<_>>();
+
+        let wip = Some("Work in progress".to_owned());
+        assert_eq!(
+            images,
+            vec![
+                ("/final.jpg".into(), Some("The finished board".into())),
+                ("/build/1.jpg".into(), wip.clone()),
+                ("/build/2.jpg".into(), wip),
+            ]
+        );
+        assert!(matches!(
+            expand_gallery(&root, "./missing/*.png"),
+            Err(GalleryError::NoMatches(_))
+        ));
+        assert!(matches!(
+            expand_gallery(&root, "/etc/passwd"),
+            Err(GalleryError::NotLocal(_))
+        ));
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut body = vec![];
+        image::DynamicImage::new_rgb8(width, height)
+            .write_to(&mut Cursor::new(&mut body), ImageFormat::Png)
+            .unwrap();
+        body
+    }
+
+    #[test]
+    pub fn grid_shows_thumbnails_and_links_full_images() {
+        let root = VfsPath::new(MemoryFS::new());
+        for (name, body) in [("big.png", png(800, 400)), ("small.png", png(100, 100))] {
+            root.join(name)
+                .unwrap()
+                .create_file()
+                .unwrap()
+                .write_all(&body)
+                .unwrap();
+        }
+        let media = MediaRegistry::new("/static".into(), VfsPath::new(MemoryFS::new()));
+        let links = LinkTargets::default();
+        let ctx = TransformContext::new(root, &media, &links);
+        let arena = Arena::new();
+        let md = "```gallery\n./big.png Big\n./small.png\n```\n";
+        let ast = parse_document(&arena, md, &make_md_options());
+
+        apply_galleries(&ctx, ast).unwrap();
+
+        let mut html = vec![];
+        format_html(ast, &make_md_options(), &mut html).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        let big = format!("/static/{}/big.png", content_hash(&png(800, 400)));
+        let small = format!("/static/{}/small.png", content_hash(&png(100, 100)));
+        assert!(html.contains(&format!(r#"href="{big}""#)), "html = {html}");
+        assert!(!html.contains(&format!(r#"src="{big}""#)), "html = {html}");
+        assert!(html.contains("/big.thumb.png\""), "html = {html}");
+        assert!(html.contains(&format!(r#"src="{small}""#)), "html = {html}");
+
+        let thumbnail = make_thumbnail(&Media {
+            filename: Some("big.png".into()),
+            mimetype: None,
+            body: png(800, 400),
+        })
+        .unwrap();
+        let decoded = image::load_from_memory(&thumbnail.body).unwrap();
+        assert_eq!(
+            (decoded.width(), decoded.height()),
+            (THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        );
+        assert_eq!(media.into_files().len(), 3);
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::{BTreeMap, HashMap};
+
+use base64::Engine;
+use comrak::{adapters::SyntaxHighlighterAdapter, plugins::syntect::SyntectAdapter};
+use maud::{html, Markup, PreEscaped};
+use serde::Deserialize;
+
+use crate::{errors::Errors, media::Media};
+
+use super::{
+    common::{TransformContext, TransformedContent},
+    markdown::{transform_markdown, MarkdownError},
+    statistics::DocumentStatistics,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum JupyterError {
+    #[error("invalid notebook: {0:#}")]
+    Parse(#[from] serde_path_to_error::Error<serde_json::Error>),
+
+    #[error("in markdown cell {cell}: {errors}")]
+    Markdown {
+        cell: usize,
+        errors: Errors<MarkdownError>,
+    },
+
+    #[error("image output of cell {cell} is not valid base64: {error}")]
+    Base64 {
+        cell: usize,
+        error: base64::DecodeError,
+    },
+
+    #[error("could not upload output of cell {0}: {1}")]
+    Upload(usize, anyhow::Error),
+}
+
+/// A notebook in nbformat 4. Only what gets rendered is parsed.
+#[derive(Deserialize, Debug)]
+pub struct Notebook {
+    pub cells: Vec<Cell>,
+
+    #[serde(default)]
+    pub metadata: NotebookMetadata,
+}
+
+#[derive(Deserialize, Default, Debug)]
+pub struct NotebookMetadata {
+    pub language_info: Option<LanguageInfo>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LanguageInfo {
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "cell_type", rename_all = "lowercase")]
+pub enum Cell {
+    Markdown {
+        source: MultilineString,
+    },
+    Code {
+        source: MultilineString,
+        execution_count: Option<u32>,
+        #[serde(default)]
+        outputs: Vec<Output>,
+    },
+    Raw {},
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "output_type", rename_all = "snake_case")]
+pub enum Output {
+    Stream { name: String, text: MultilineString },
+    DisplayData { data: MimeBundle },
+    ExecuteResult { data: MimeBundle },
+    Error { traceback: Vec<String> },
+}
+
+/// The same output in different formats, keyed by mimetype.
+pub type MimeBundle = BTreeMap<String, serde_json::Value>;
+
+/// Notebooks store text either as one string, or as a list of lines.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum MultilineString {
+    One(String),
+    Lines(Vec<String>),
+}
+
+impl MultilineString {
+    pub fn join(&self) -> String {
+        match self {
+            MultilineString::One(s) => s.clone(),
+            MultilineString::Lines(ls) => ls.concat(),
+        }
+    }
+}
+
+/// Render a Jupyter notebook into HTML. Markdown cells are transformed like
+/// any other markdown, and image outputs are uploaded as media.
+#[tracing::instrument(skip_all)]
+pub async fn transform_notebook(
+    ctx: &TransformContext<'_>,
+    raw: &str,
+) -> Result<TransformedContent, JupyterError> {
+    let notebook: Notebook =
+        serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_str(raw))?;
+    let lang = notebook
+        .metadata
+        .language_info
+        .map(|l| l.name)
+        .unwrap_or_else(|| "python".into());
+    let syntect = SyntectAdapter::new(Some("base16-ocean.dark"));
+
+    let mut stats = DocumentStatistics::default();
+    let mut cells = vec![];
+    for (i, cell) in notebook.cells.iter().enumerate() {
+        let n = i + 1;
+        match cell {
+            Cell::Markdown { source } => {
+                let md = transform_markdown(ctx, &source.join())
+                    .await
+                    .map_err(|errors| JupyterError::Markdown { cell: n, errors })?;
+                stats += md.stats;
+                cells.push(html! {
+                    div.nb-cell.nb-markdown { (PreEscaped(md.html)) }
+                });
+            }
+            Cell::Code {
+                source,
+                execution_count,
+                outputs,
+            } => {
+                stats.code_blocks += 1;
+                let outputs = outputs
+                    .iter()
+                    .map(|o| render_output(ctx, n, o, &mut stats))
+                    .collect::<Result<Vec<_>, _>>()?;
+                cells.push(html! {
+                    div.nb-cell.nb-code {
+                        div.nb-input {
+                            span.nb-prompt { "In [" (execution_count.map(|c| c.to_string()).unwrap_or_default()) "]:" }
+                            (highlight(&syntect, &lang, &source.join()))
+                        }
+                        @if !outputs.is_empty() {
+                            div.nb-outputs { @for o in outputs { (o) } }
+                        }
+                    }
+                });
+            }
+            Cell::Raw {} => (),
+        }
+    }
+
+    Ok(TransformedContent {
+        html: html! { div.notebook { @for c in cells { (c) } } }.into_string(),
+        stats,
+    })
+}
+
+fn render_output(
+    ctx: &TransformContext<'_>,
+    cell: usize,
+    output: &Output,
+    stats: &mut DocumentStatistics,
+) -> Result<Markup, JupyterError> {
+    let data = match output {
+        Output::Stream { name, text } => {
+            return Ok(html! { pre class={"nb-stream nb-" (name)} { (text.join()) } })
+        }
+        Output::Error { traceback } => {
+            return Ok(html! { pre.nb-error { (strip_ansi(&traceback.join("\n"))) } })
+        }
+        Output::DisplayData { data } | Output::ExecuteResult { data } => data,
+    };
+
+    let text = |mimetype: &str| {
+        data.get(mimetype)
+            .and_then(|v| serde_json::from_value::<MultilineString>(v.clone()).ok())
+            .map(|s| s.join())
+    };
+    let plain = text("text/plain");
+
+    let image = if let Some(svg) = text("image/svg+xml") {
+        Some((svg.into_bytes(), "svg", mime::IMAGE_SVG))
+    } else if let Some(png) = text("image/png") {
+        let png = png.split_whitespace().collect::<String>();
+        let body = base64::engine::general_purpose::STANDARD
+            .decode(png)
+            .map_err(|error| JupyterError::Base64 { cell, error })?;
+        Some((body, "png", mime::IMAGE_PNG))
+    } else {
+        None
+    };
+    if let Some((body, ext, mimetype)) = image {
+        let url = ctx
+            .media()
+            .upload_media(Media {
+                filename: Some(format!("cell-{cell}.{ext}")),
+                mimetype: Some(mimetype),
+                body,
+            })
+            .map_err(|e| JupyterError::Upload(cell, e))?;
+        stats.images += 1;
+        let alt = plain.unwrap_or_else(|| format!("Output of cell {cell}"));
+        return Ok(html! { img.nb-image src=(url) alt=(alt); });
+    }
+
+    if let Some(h) = text("text/html") {
+        return Ok(html! { div.nb-html { (PreEscaped(h)) } });
+    }
+    Ok(html! { pre.nb-text { (plain.unwrap_or_default()) } })
+}
+
+/// Highlight code the same way code blocks in markdown are.
+fn highlight(syntect: &SyntectAdapter, lang: &str, code: &str) -> Markup {
+    let mut out = vec![];
+    syntect
+        .write_pre_tag(&mut out, HashMap::from([("lang".into(), lang.into())]))
+        .unwrap();
+    syntect.write_code_tag(&mut out, HashMap::new()).unwrap();
+    syntect
+        .write_highlighted(&mut out, Some(lang), code)
+        .unwrap();
+    out.extend_from_slice(b"</code></pre>");
+    PreEscaped(String::from_utf8(out).unwrap())
+}
+
+/// Remove terminal color codes, which tracebacks are full of.
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip `ESC [ params letter`
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use vfs::{MemoryFS, VfsPath};
+
+    use crate::{media::MediaRegistry, transform::links::LinkTargets};
+
+    use super::*;
+
+    // A 1x1 transparent PNG
+    const PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+    #[tokio::test]
+    pub async fn renders_cells_and_outputs() {
+        let notebook = json!({
+            "metadata": { "language_info": { "name": "python" } },
+            "nbformat": 4,
+            "nbformat_minor": 5,
+            "cells": [
+                { "cell_type": "markdown", "metadata": {}, "source": ["# Results\n", "Some *analysis*."] },
+                {
+                    "cell_type": "code",
+                    "execution_count": 3,
+                    "metadata": {},
+                    "source": "print('hi')\ndf.head()",
+                    "outputs": [
+                        { "output_type": "stream", "name": "stdout", "text": ["hi\n"] },
+                        { "output_type": "execute_result", "execution_count": 3, "metadata": {},
+                          "data": { "text/html": "<table><tr><td>1</td></tr></table>", "text/plain": "   a\n0  1" } },
+                        { "output_type": "display_data", "metadata": {},
+                          "data": { "image/png": PNG, "text/plain": "<Figure size 640x480>" } },
+                        { "output_type": "error", "ename": "ValueError", "evalue": "bad",
+                          "traceback": ["\u{1b}[0;31mValueError\u{1b}[0m: bad"] },
+                    ]
+                },
+                { "cell_type": "raw", "metadata": {}, "source": "ignored" },
+            ]
+        });
+        let media = MediaRegistry::new("/static".into(), VfsPath::new(MemoryFS::new()));
+        let links = LinkTargets::default();
+        let ctx = TransformContext::new(VfsPath::new(MemoryFS::new()), &media, &links);
+
+        let out = transform_notebook(&ctx, &notebook.to_string())
+            .await
+            .unwrap();
+
+        let html = out.html;
+        assert!(html.contains("<em>analysis</em>"), "html = {html}");
+        assert!(html.contains("In [3]:"), "html = {html}");
+        assert!(html.contains("<pre lang=\"python\""), "html = {html}");
+        assert!(
+            html.contains(r#"<pre class="nb-stream nb-stdout">hi"#),
+            "html = {html}"
+        );
+        assert!(html.contains("<td>1</td>"), "html = {html}");
+        assert!(
+            html.contains("/cell-2.png\" alt=\"&lt;Figure size 640x480&gt;\""),
+            "html = {html}"
+        );
+        assert!(
+            html.contains("<pre class=\"nb-error\">ValueError: bad</pre>"),
+            "html = {html}"
+        );
+        assert!(!html.contains("ignored"), "html = {html}");
+        assert_eq!(media.into_files().len(), 1);
+        assert_eq!((out.stats.code_blocks, out.stats.images), (1, 1));
+    }
+
+    #[tokio::test]
+    pub async fn reports_invalid_notebooks_with_path() {
+        let media = MediaRegistry::new("/static".into(), VfsPath::new(MemoryFS::new()));
+        let links = LinkTargets::default();
+        let ctx = TransformContext::new(VfsPath::new(MemoryFS::new()), &media, &links);
+        let notebook = json!({ "cells": [{ "cell_type": "code", "source": 5 }] });
+
+        let Err(e) = transform_notebook(&ctx, &notebook.to_string()).await else {
+            panic!("expected an error");
+        };
+
+        assert!(e.to_string().contains("cells[0]"), "e = {e}");
+    }
+}
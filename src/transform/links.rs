@@ -0,0 +1,360 @@
+use std::{cell::RefCell, collections::HashMap, fmt::Display, str::FromStr};
+
+use comrak::{
+    arena_tree::Node,
+    nodes::{Ast, AstNode, LineColumn, NodeLink, NodeValue, Sourcepos},
+    Arena,
+};
+use itertools::Itertools;
+
+use crate::errors::Errors;
+
+use super::markdown::{MarkdownError, MarkdownErrorKind};
+
+/// URL scheme for internal links, as in `[text](seams:post:hello-world)`.
+pub const INTERNAL_LINK_SCHEME: &str = "seams:";
+
+/// The kind of document an internal link points to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LinkKind {
+    Post,
+    Project,
+    Page,
+    Computer,
+}
+
+impl FromStr for LinkKind {
+    type Err = LinkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "post" => Ok(LinkKind::Post),
+            "project" => Ok(LinkKind::Project),
+            "page" => Ok(LinkKind::Page),
+            "computer" => Ok(LinkKind::Computer),
+            k => Err(LinkError::UnknownKind(k.into())),
+        }
+    }
+}
+
+impl Display for LinkKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkKind::Post => write!(f, "post"),
+            LinkKind::Project => write!(f, "project"),
+            LinkKind::Page => write!(f, "page"),
+            LinkKind::Computer => write!(f, "computer"),
+        }
+    }
+}
+
+/// A reference to another document, like `post:hello-world`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InternalLink {
+    pub kind: LinkKind,
+    pub name: String,
+}
+
+impl FromStr for InternalLink {
+    type Err = LinkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((kind, name)) = s.split_once(':') else {
+            return Err(LinkError::Malformed(s.into()));
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(LinkError::Malformed(s.into()));
+        }
+
+        Ok(Self {
+            kind: kind.trim().parse()?,
+            name: name.into(),
+        })
+    }
+}
+
+impl Display for InternalLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.kind, self.name)
+    }
+}
+
+/// Where an internal link resolves to.
+#[derive(Clone, Debug)]
+pub struct LinkTarget {
+    pub href: String,
+    pub title: String,
+}
+
+/// Every document that can be linked to, by kind and name.
+#[derive(Default, Clone, Debug)]
+pub struct LinkTargets {
+    targets: HashMap<InternalLink, Vec<LinkTarget>>,
+}
+
+impl LinkTargets {
+    pub fn insert(&mut self, kind: LinkKind, name: impl Into<String>, target: LinkTarget) {
+        let link = InternalLink {
+            kind,
+            name: name.into(),
+        };
+        self.targets.entry(link).or_default().push(target);
+    }
+
+    pub fn resolve(&self, link: &InternalLink) -> Result<&LinkTarget, LinkError> {
+        match self.targets.get(link).map(|ts| ts.as_slice()) {
+            None | Some([]) => Err(LinkError::UnknownTarget(link.clone())),
+            Some([t]) => Ok(t),
+            Some(ts) => Err(LinkError::AmbiguousTarget(
+                link.clone(),
+                ts.iter().map(|t| t.href.clone()).collect(),
+            )),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum LinkError {
+    #[error("malformed internal link {0:?}, expected something like post:hello-world")]
+    Malformed(String),
+
+    #[error("unknown link kind {0:?}, expected one of post, project, page, computer")]
+    UnknownKind(String),
+
+    #[error("link to {0} does not match any document")]
+    UnknownTarget(InternalLink),
+
+    #[error("link to {0} is ambiguous, could be any of {1:?}")]
+    AmbiguousTarget(InternalLink, Vec<String>),
+}
+
+/// Resolve `[[kind:name]]` wiki-style links and `seams:kind:name` URLs into
+/// real hrefs.
+///
+/// Wiki-style links may also specify their text, as in `[[post:hello-world|my post]]`.
+/// Otherwise, the title of the linked document is used.
+#[tracing::instrument(skip_all)]
+pub fn resolve_internal_links<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    links: &LinkTargets,
+    root: &'a AstNode<'a>,
+) -> Result<(), Errors<MarkdownError>> {
+    let mut errors = Errors::new();
+
+    // Collect first, because we will be inserting nodes as we go
+    for n in root.descendants().collect_vec() {
+        let mut ast = n.data.borrow_mut();
+        let sourcepos = ast.sourcepos;
+        match &mut ast.value {
+            NodeValue::Link(l) if l.url.starts_with(INTERNAL_LINK_SCHEME) => {
+                match resolve_url(links, &l.url[INTERNAL_LINK_SCHEME.len()..]) {
+                    Ok(href) => l.url = href,
+                    Err(e) => errors.push(MarkdownError::new(sourcepos, e.into())),
+                }
+            }
+            NodeValue::Text(t) if t.contains("[[") => {
+                let text = std::mem::take(t);
+                drop(ast);
+                split_wikilinks(arena, links, n, &text, sourcepos, &mut errors);
+            }
+            _ => (),
+        }
+    }
+
+    errors.into_result()?;
+
+    Ok(())
+}
+
+/// Find the targets of every root-relative link in a piece of HTML,
+/// without their query strings, fragments, or trailing slashes.
+pub fn find_internal_hrefs(html: &str) -> impl Iterator<Item = &str> {
+    html.split("href=").skip(1).filter_map(|rest| {
+        let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let rest = &rest[1..];
+        let href = &rest[..rest.find(quote)?];
+        let href = href.split(['#', '?']).next().unwrap_or_default();
+
+        match href.strip_prefix('/') {
+            Some(path) if !path.starts_with('/') => Some(href.trim_end_matches('/')),
+            _ => None,
+        }
+    })
+}
+
+fn resolve_url(links: &LinkTargets, url: &str) -> Result<String, LinkError> {
+    let (link, fragment) = match url.split_once('#') {
+        Some((l, f)) => (l, Some(f)),
+        None => (url, None),
+    };
+    let target = links.resolve(&link.parse()?)?;
+
+    Ok(match fragment {
+        Some(f) => format!("{}#{}", target.href, f),
+        None => target.href.clone(),
+    })
+}
+
+/// Replace a text node with a sequence of text and link nodes.
+fn split_wikilinks<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    links: &LinkTargets,
+    node: &'a AstNode<'a>,
+    text: &str,
+    sourcepos: Sourcepos,
+    errors: &mut Errors<MarkdownError>,
+) {
+    let new_node = |value: NodeValue, offset: usize| -> &'a AstNode<'a> {
+        let start = LineColumn {
+            line: sourcepos.start.line,
+            column: sourcepos.start.column + offset,
+        };
+        arena.alloc(Node::new(RefCell::new(Ast::new(value, start))))
+    };
+
+    let mut rest = text;
+    let mut offset = 0;
+    while let Some(start) = rest.find("[[") {
+        let Some(len) = rest[start..].find("]]") else {
+            break;
+        };
+        let inner = &rest[start + 2..start + len];
+
+        if start > 0 {
+            node.insert_before(new_node(NodeValue::Text(rest[..start].into()), offset));
+        }
+
+        let (link, label) = match inner.split_once('|') {
+            Some((l, t)) => (l, Some(t.trim())),
+            None => (inner, None),
+        };
+        let resolved = link
+            .parse::<InternalLink>()
+            .and_then(|l| links.resolve(&l).cloned());
+
+        match resolved {
+            Ok(target) => {
+                let link_node = new_node(
+                    NodeValue::Link(NodeLink {
+                        url: target.href,
+                        title: String::new(),
+                    }),
+                    offset + start,
+                );
+                let label = label.map(String::from).unwrap_or(target.title);
+                link_node.append(new_node(NodeValue::Text(label), offset + start + 2));
+                node.insert_before(link_node);
+            }
+            Err(e) => {
+                let mut pos = sourcepos;
+                pos.start.column += offset + start;
+                pos.end = LineColumn {
+                    line: pos.start.line,
+                    column: pos.start.column + len + 1,
+                };
+                errors.push(MarkdownError::new(pos, MarkdownErrorKind::Link(e)));
+
+                // Leave the original text alone
+                let original = &rest[start..start + len + 2];
+                node.insert_before(new_node(NodeValue::Text(original.into()), offset + start));
+            }
+        }
+
+        offset += start + len + 2;
+        rest = &rest[start + len + 2..];
+    }
+
+    node.data.borrow_mut().value = NodeValue::Text(rest.into());
+    node.data.borrow_mut().sourcepos.start.column += offset;
+}
+
+#[cfg(test)]
+mod tests {
+    use comrak::{format_html, parse_document};
+
+    use crate::transform::markdown::make_md_options;
+
+    use super::*;
+
+    fn link_targets() -> LinkTargets {
+        let mut links = LinkTargets::default();
+        links.insert(
+            LinkKind::Post,
+            "hello-world",
+            LinkTarget {
+                href: "/2022/01/02/0/hello-world".into(),
+                title: "Hello World".into(),
+            },
+        );
+        links.insert(
+            LinkKind::Project,
+            "kiwibot",
+            LinkTarget {
+                href: "/projects/kiwibot".into(),
+                title: "Kiwibot".into(),
+            },
+        );
+        links
+    }
+
+    fn render(md: &str) -> (String, Result<(), Errors<MarkdownError>>) {
+        let arena = Arena::new();
+        let options = make_md_options();
+        let root = parse_document(&arena, md, &options);
+
+        let result = resolve_internal_links(&arena, &link_targets(), root);
+
+        let mut html = vec![];
+        format_html(root, &options, &mut html).unwrap();
+        (String::from_utf8(html).unwrap(), result)
+    }
+
+    #[test]
+    pub fn resolves_wikilinks() {
+        let (html, result) =
+            render("see [[post:hello-world]] and [[project:kiwibot|the robot]] ok");
+
+        result.unwrap();
+        assert!(
+            html.contains(
+                r#"see <a href="/2022/01/02/0/hello-world">Hello World</a> and <a href="/projects/kiwibot">the robot</a> ok"#
+            ),
+            "html = {html}"
+        );
+    }
+
+    #[test]
+    pub fn resolves_seams_urls() {
+        let (html, result) = render("[the bot](seams:project:kiwibot#design)");
+
+        result.unwrap();
+        assert!(
+            html.contains(r#"<a href="/projects/kiwibot#design">the bot</a>"#),
+            "html = {html}"
+        );
+    }
+
+    #[test]
+    pub fn finds_internal_hrefs() {
+        let html = r#"<a href="/projects/kiwibot/#design">a</a> <a href='/blog?page=2'>b</a>
+            <a href="https://example.com">c</a> <a href="//cdn.example.com">d</a>"#;
+
+        let hrefs = find_internal_hrefs(html).collect_vec();
+
+        assert_eq!(hrefs, vec!["/projects/kiwibot", "/blog"]);
+    }
+
+    #[test]
+    pub fn reports_unknown_targets_with_position() {
+        let (_, result) = render("line one\n\nsome [[post:nonexistent]] link");
+
+        let errors = result.unwrap_err().into_iter().collect_vec();
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0].to_string().starts_with("Error at 3:6-"),
+            "error = {}",
+            errors[0]
+        );
+    }
+}
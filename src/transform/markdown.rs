@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use comrak::{
     format_html_with_plugins,
     nodes::{AstNode, LineColumn, NodeHtmlBlock, NodeLink, NodeValue, Sourcepos},
@@ -10,7 +12,7 @@ use futures::TryFutureExt;
 use itertools::Itertools;
 
 use tracing::trace;
-use vfs::VfsError;
+use vfs::{VfsError, VfsPath};
 
 use crate::{
     errors::Errors,
@@ -18,10 +20,14 @@ use crate::{
 };
 
 use super::{
-    common::TransformContext,
+    common::{TransformContext, TransformedContent},
+    gallery::{apply_galleries, GalleryError},
     graphviz::{transform_graphviz, GraphvizError},
     katex::KatexError,
     katex_md::apply_katex,
+    links::{resolve_internal_links, LinkError},
+    remote::mirror_remote_images,
+    statistics::DocumentStatistics,
 };
 
 pub fn make_md_options() -> comrak::Options {
@@ -43,12 +49,12 @@ pub fn make_md_options() -> comrak::Options {
 
 /// Transform markdown into HTML. May be computationally expensive.
 ///
-/// Returns HTML containing unicode.
+/// Returns HTML containing unicode, along with statistics about the document.
 #[tracing::instrument(skip_all)]
 pub async fn transform_markdown<'a>(
     ctx: &'a TransformContext<'a>,
     raw: &'a str,
-) -> Result<String, Errors<MarkdownError>> {
+) -> Result<TransformedContent, Errors<MarkdownError>> {
     let arena = Arena::new();
 
     let md_options = make_md_options();
@@ -65,17 +71,34 @@ pub async fn transform_markdown<'a>(
         .unwrap();
 
     let root = parse_document(&arena, raw, &md_options);
+    let stats = DocumentStatistics::from_markdown(ctx.content_root(), root);
 
     let mut errors = Errors::new();
 
+    if let Err(es) = resolve_internal_links(&arena, ctx.links(), root) {
+        errors.extend(es)
+    }
+
     if let Err(es) = apply_graphviz(&ctx.media(), root).await {
         errors.extend(es)
     }
 
-    if let Err(es) = relink_images(ctx, root) {
+    if let Err(es) = mirror_remote_images(ctx, root).await {
         errors.extend(es)
     }
 
+    if let Err(es) = apply_galleries(ctx, root) {
+        errors.extend(es)
+    }
+
+    let posters = match relink_images(ctx, root) {
+        Ok(p) => p,
+        Err(es) => {
+            errors.extend(es);
+            HashMap::new()
+        }
+    };
+
     apply_katex(root).await.map_err(|e| {
         e.into_iter()
             .map(|e| MarkdownError {
@@ -88,31 +111,50 @@ pub async fn transform_markdown<'a>(
             .collect::<Errors<MarkdownError>>()
     })?;
 
-    transform_image_to_picture(root);
+    transform_image_to_picture(root, &posters);
 
     let mut bw = Vec::new();
     format_html_with_plugins(root, &md_options, &mut bw, &plugins).unwrap();
-    Ok(String::from_utf8(bw).unwrap())
+    Ok(TransformedContent {
+        html: String::from_utf8(bw).unwrap(),
+        stats,
+    })
+}
+
+/// Whether an image URL points to a file next to the document, and should be uploaded.
+pub fn is_local_image(url: &str) -> bool {
+    url.trim().starts_with("./")
 }
 
 /// Transform links in images into what they should be, and upload them.
+///
+/// Returns the uploaded poster frame of every video that has one.
 #[tracing::instrument(skip_all)]
 pub fn relink_images<'a>(
     ctx: &'a TransformContext,
     root: &'a AstNode<'a>,
-) -> Result<(), Errors<MarkdownError>> {
+) -> Result<HashMap<String, String>, Errors<MarkdownError>> {
     let mut errors = Errors::new();
+    let mut posters = HashMap::new();
 
     for n in root.descendants() {
         let mut ast = n.data.borrow_mut();
         match &mut ast.value {
-            NodeValue::Image(link) if link.url.trim().starts_with("./") => {
-                let mut f = move || {
-                    let image = ctx.content_root().join(&link.url)?;
-                    link.url = ctx
+            NodeValue::Image(link) if is_local_image(&link.url) => {
+                let mut f = || {
+                    let file = ctx.content_root().join(&link.url)?;
+                    let url = ctx
                         .media()
-                        .upload_media(image)
+                        .upload_media(file.clone())
                         .map_err(MarkdownErrorKind::Image)?;
+                    if let Some(poster) = find_poster(&file)? {
+                        let poster = ctx
+                            .media()
+                            .upload_media(poster)
+                            .map_err(MarkdownErrorKind::Image)?;
+                        posters.insert(url.clone(), poster);
+                    }
+                    link.url = url;
                     Ok(())
                 };
                 if let Err(e) = f() {
@@ -125,7 +167,52 @@ pub fn relink_images<'a>(
 
     errors.into_result()?;
 
-    Ok(())
+    Ok(posters)
+}
+
+/// What kind of element a `![]()` target should be embedded with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbedKind {
+    Image,
+    Video,
+    Audio,
+}
+
+impl EmbedKind {
+    pub fn from_url(url: &str) -> Self {
+        let path = url.split(['?', '#']).next().unwrap_or_default();
+        let Some(mime) = mime_guess::from_path(path).first() else {
+            return EmbedKind::Image;
+        };
+        match mime.type_() {
+            mime::VIDEO => EmbedKind::Video,
+            mime::AUDIO => EmbedKind::Audio,
+            _ => EmbedKind::Image,
+        }
+    }
+}
+
+/// Image formats that a video's poster frame can be in.
+const POSTER_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+
+/// Find the poster frame for a video, which is an image next to it with the
+/// same name, like `clip.jpg` for `clip.mp4`.
+fn find_poster(file: &VfsPath) -> Result<Option<VfsPath>, VfsError> {
+    let filename = file.filename();
+    if EmbedKind::from_url(&filename) != EmbedKind::Video {
+        return Ok(None);
+    }
+
+    let stem = filename
+        .rsplit_once('.')
+        .map_or(filename.as_str(), |(s, _)| s);
+    for ext in POSTER_EXTENSIONS {
+        let poster = file.parent().join(format!("{stem}.{ext}"))?;
+        if poster.exists()? {
+            return Ok(Some(poster));
+        }
+    }
+    Ok(None)
 }
 
 #[tracing::instrument(skip_all)]
@@ -199,7 +286,7 @@ fn parse_graphviz_info(infostr: &str) -> Option<GraphvizInfo> {
 }
 
 #[tracing::instrument(skip_all)]
-pub fn transform_image_to_picture<'a>(root: &'a AstNode<'a>) {
+pub fn transform_image_to_picture<'a>(root: &'a AstNode<'a>, posters: &HashMap<String, String>) {
     use maud::html;
 
     let mut to_visit = root.children().collect_vec();
@@ -221,10 +308,18 @@ pub fn transform_image_to_picture<'a>(root: &'a AstNode<'a>) {
 
                 let markup = html! {
                     figure {
-                        picture {
-                            a href=(l.url) {
-                                img src=(l.url) alt=(alt);
-                            }
+                        @match EmbedKind::from_url(&l.url) {
+                            EmbedKind::Image => picture {
+                                a href=(l.url) {
+                                    img src=(l.url) alt=(alt);
+                                }
+                            },
+                            EmbedKind::Video => video controls preload="metadata" src=(l.url) poster=[posters.get(&l.url)] {
+                                a href=(l.url) { (alt) }
+                            },
+                            EmbedKind::Audio => audio controls preload="metadata" src=(l.url) {
+                                a href=(l.url) { (alt) }
+                            },
                         }
                         @if l.title.len() > 0 {
                             figcaption {
@@ -258,6 +353,24 @@ impl MarkdownError {
     pub fn new(pos: Sourcepos, kind: MarkdownErrorKind) -> Self {
         Self { pos, kind }
     }
+
+    pub fn pos(&self) -> Sourcepos {
+        self.pos
+    }
+
+    pub fn kind(&self) -> &MarkdownErrorKind {
+        &self.kind
+    }
+
+    /// Shift the position down by some lines, for when the markdown didn't
+    /// start at the top of its file. Unknown positions stay unknown.
+    pub fn offset_lines(mut self, lines: usize) -> Self {
+        if self.pos.start.line > 0 {
+            self.pos.start.line += lines;
+            self.pos.end.line += lines;
+        }
+        self
+    }
 }
 
 impl std::fmt::Display for MarkdownError {
@@ -275,15 +388,23 @@ pub enum MarkdownErrorKind {
     #[error("image error: {0}")]
     Image(anyhow::Error),
 
+    #[error("gallery error: {0}")]
+    Gallery(#[from] GalleryError),
+
     #[error("fs error: {0}")]
     Vfs(#[from] VfsError),
 
     #[error("katex error: {0}")]
     Katex(#[from] KatexError),
+
+    #[error("link error: {0}")]
+    Link(#[from] LinkError),
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::media::content_hash;
+
     use super::*;
 
     #[test]
@@ -294,7 +415,7 @@ mod tests {
         let root = parse_document(&mut arena, md, &options);
 
         eprintln!("BEFORE TRANFORM: {root:#?}");
-        transform_image_to_picture(root);
+        transform_image_to_picture(root, &HashMap::new());
         eprintln!("AFTER TRANFORM: {root:#?}");
 
         let mut html = vec![];
@@ -308,4 +429,55 @@ mod tests {
             html
         );
     }
+
+    #[test]
+    pub fn embeds_video_and_audio() {
+        use vfs::MemoryFS;
+
+        use crate::transform::links::LinkTargets;
+
+        let content = VfsPath::new(MemoryFS::new());
+        for name in ["clip.mp4", "clip.png", "song.ogg"] {
+            content
+                .join(name)
+                .unwrap()
+                .create_file()
+                .unwrap()
+                .write_all(name.as_bytes())
+                .unwrap();
+        }
+        let media = MediaRegistry::new("/static".into(), VfsPath::new(MemoryFS::new()));
+        let links = LinkTargets::default();
+        let ctx = TransformContext::new(content, &media, &links);
+
+        let arena = Arena::new();
+        let md = "![demo](./clip.mp4 \"The demo\")\n\n![song](./song.ogg)";
+        let options = make_md_options();
+        let root = parse_document(&arena, md, &options);
+
+        let posters = relink_images(&ctx, root).unwrap();
+        transform_image_to_picture(root, &posters);
+
+        let mut html = vec![];
+        format_html_with_plugins(root, &options, &mut html, &comrak::Plugins::default()).unwrap();
+        let html = String::from_utf8_lossy(&html);
+
+        let url = |name: &str| format!("/static/{}/{name}", content_hash(name.as_bytes()));
+        let video = format!(
+            r#"<video controls preload="metadata" src="{}" poster="{}">"#,
+            url("clip.mp4"),
+            url("clip.png")
+        );
+        let audio = format!(
+            r#"<audio controls preload="metadata" src="{}">"#,
+            url("song.ogg")
+        );
+        assert!(html.contains(&video), "html = {html}");
+        assert!(html.contains(&audio), "html = {html}");
+        assert!(
+            html.contains("<figcaption>The demo</figcaption>"),
+            "html = {html}"
+        );
+        assert_eq!(media.into_files().len(), 3);
+    }
 }
@@ -0,0 +1,82 @@
+//! Helpers for translating other markup languages into markdown, so that
+//! they go through the same transforms.
+
+const IMAGE_EXTENSIONS: [&str; 7] = ["png", "jpg", "jpeg", "gif", "svg", "webp", "avif"];
+
+/// Whether a link target looks like an image.
+pub fn is_image(url: &str) -> bool {
+    url.rsplit_once('.')
+        .is_some_and(|(_, ext)| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Whether a link target is a path relative to the document, without the
+/// `./` that markdown needs to treat it as a local file.
+pub fn is_bare_relative(path: &str) -> bool {
+    !path.contains(':') && !path.starts_with(['.', '/', '#'])
+}
+
+/// Display math as markdown, taking up exactly as many lines as it did in
+/// the source.
+///
+/// Each line of math must be non-blank, or it would end the paragraph.
+pub fn display_math(lines: &[&str]) -> Vec<String> {
+    let math = lines
+        .iter()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>();
+    if math.is_empty() {
+        return vec![String::new(); lines.len().max(1)];
+    }
+
+    let mut out = vec![String::new(); lines.len().max(1) - math.len().min(lines.len())];
+    let last = math.len() - 1;
+    for (i, l) in math.iter().enumerate() {
+        let open = if i == 0 { "<M>" } else { "" };
+        let close = if i == last { "</M>" } else { "" };
+        out.push(format!("{open}{}{close}", escape_math(l)));
+    }
+    out
+}
+
+/// Escape math so that markdown leaves it alone for KaTeX.
+pub fn escape_math(math: &str) -> String {
+    let mut out = String::with_capacity(math.len());
+    for c in math.chars() {
+        if c.is_ascii_punctuation() {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// A code span containing exactly `code`, even if it has backticks in it.
+pub fn code_span(code: &str) -> String {
+    let longest = code.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let ticks = "`".repeat(longest + 1);
+    let pad = if code.starts_with('`') || code.ends_with('`') {
+        " "
+    } else {
+        ""
+    };
+    format!("{ticks}{pad}{code}{pad}{ticks}")
+}
+
+/// Add a character of plain text, escaping it if markdown would treat it as
+/// syntax.
+pub fn push_escaped(out: &mut String, c: char) {
+    match c {
+        '<' => out.push_str("&lt;"),
+        '>' => out.push_str("&gt;"),
+        '\\' | '*' | '_' | '`' | '[' | ']' | '~' | '|' | '$' => {
+            out.push('\\');
+            out.push(c);
+        }
+        c => out.push(c),
+    }
+}
+
+pub fn table_separator(columns: usize) -> String {
+    format!("|{}", "---|".repeat(columns.max(1)))
+}
@@ -1,6 +1,14 @@
+pub mod asciidoc;
 pub mod common;
+pub mod gallery;
 pub mod graphviz;
+pub mod jupyter;
 pub mod katex;
 mod katex_md;
+pub mod links;
 pub mod markdown;
+mod markup;
+pub mod org;
+pub mod remote;
+pub mod rewrite;
 pub mod statistics;
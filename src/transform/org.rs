@@ -0,0 +1,530 @@
+//! Org-mode support, by translating it into markdown so that it goes through
+//! the same transforms as everything else.
+//!
+//! Lines are translated one-to-one wherever possible, so that positions in
+//! errors still point at the right place in the original file.
+
+use crate::errors::Errors;
+
+use super::{
+    common::{TransformContext, TransformedContent},
+    markdown::{transform_markdown, MarkdownError},
+    markup::{
+        code_span, display_math, escape_math, is_bare_relative, is_image, push_escaped,
+        table_separator,
+    },
+};
+
+/// Transform Org into HTML, the same way as markdown.
+#[tracing::instrument(skip_all)]
+pub async fn transform_org(
+    ctx: &TransformContext<'_>,
+    raw: &str,
+) -> Result<TransformedContent, Errors<MarkdownError>> {
+    transform_markdown(ctx, &org_to_markdown(raw)).await
+}
+
+/// What kind of block the translator is inside of.
+enum Block {
+    Normal,
+    /// A source or example block, copied verbatim.
+    Verbatim,
+    Quote,
+    /// Display math, ended by the given delimiter.
+    Math(String),
+}
+
+/// Translate an Org document into equivalent markdown.
+pub fn org_to_markdown(org: &str) -> String {
+    let mut out: Vec<String> = vec![];
+    let mut block = Block::Normal;
+    let mut math: Vec<&str> = vec![];
+    let mut table_columns: Option<usize> = None;
+
+    for line in org.lines() {
+        let trimmed = line.trim();
+        let keyword = trimmed.to_ascii_lowercase();
+
+        match &block {
+            Block::Verbatim => {
+                if keyword.starts_with("#+end_") {
+                    out.push("```".into());
+                    block = Block::Normal;
+                } else {
+                    // Org escapes lines that would otherwise be syntax with a comma
+                    let unescaped = match line.trim_start().strip_prefix(',') {
+                        Some(rest) if rest.starts_with('*') || rest.starts_with("#+") => rest,
+                        _ => line,
+                    };
+                    out.push(unescaped.into());
+                }
+                continue;
+            }
+            Block::Math(end) => {
+                match trimmed.strip_suffix(end.as_str()) {
+                    Some(rest) => {
+                        // Environments are ended by part of the math itself
+                        math.push(if end.starts_with("\\end") {
+                            trimmed
+                        } else {
+                            rest
+                        });
+                        out.extend(display_math(&math));
+                        math.clear();
+                        block = Block::Normal;
+                    }
+                    None => math.push(line),
+                }
+                continue;
+            }
+            Block::Normal | Block::Quote => (),
+        }
+
+        // Tables
+        if trimmed.starts_with('|') {
+            let separator = trimmed.starts_with("|-");
+            let cells = table_cells(trimmed);
+            match table_columns {
+                // Markdown tables need a header, so make one out of the first row
+                None if separator => continue,
+                None => {
+                    table_columns = Some(cells.len());
+                    out.push(table_row(&cells));
+                    out.push(table_separator(cells.len()));
+                }
+                // The header's separator was already added
+                Some(_) if separator => {
+                    if out.last().is_some_and(|l| l.starts_with("|-")) {
+                        out.pop();
+                        out.push(table_separator(table_columns.unwrap()));
+                    } else {
+                        out.push(String::new());
+                    }
+                }
+                Some(_) => out.push(table_row(&cells)),
+            }
+            continue;
+        }
+        if table_columns.take().is_some() {
+            // Separators don't take up a line in markdown, so account for them
+            if let Some(i) = out.iter().rposition(|l| l.starts_with("|-")) {
+                if out[i + 1..].is_empty() {
+                    out.push(String::new());
+                }
+            }
+        }
+
+        let translated = if keyword.starts_with("#+begin_src") {
+            block = Block::Verbatim;
+            let lang = trimmed.split_whitespace().nth(1).unwrap_or_default();
+            format!("```{lang}")
+        } else if keyword.starts_with("#+begin_example") {
+            block = Block::Verbatim;
+            "```".into()
+        } else if keyword.starts_with("#+begin_quote") {
+            block = Block::Quote;
+            String::new()
+        } else if keyword.starts_with("#+end_quote") {
+            block = Block::Normal;
+            String::new()
+        } else if keyword.starts_with("#+") || trimmed == "#" || trimmed.starts_with("# ") {
+            // Keywords and comments
+            String::new()
+        } else if let Some((start, end)) = display_math_start(trimmed)
+            // Math followed by more text is inline
+            .filter(|(s, end)| {
+                let rest = &trimmed[*s..];
+                rest.ends_with(end.as_str()) || !rest.contains(end.as_str())
+            })
+        {
+            let rest = &trimmed[start..];
+            match rest.strip_suffix(end.as_str()).filter(|_| !rest.is_empty()) {
+                Some(inner) => display_math(&[inner]).concat(),
+                None => {
+                    math.push(rest);
+                    block = Block::Math(end);
+                    continue;
+                }
+            }
+        } else {
+            translate_line(line)
+        };
+
+        out.push(match block {
+            Block::Quote if !translated.is_empty() => format!("> {translated}"),
+            _ => translated,
+        });
+    }
+
+    // Close anything left open, instead of losing it
+    match block {
+        Block::Verbatim => out.push("```".into()),
+        Block::Math(_) => out.extend(display_math(&math)),
+        Block::Normal | Block::Quote => (),
+    }
+
+    out.join("\n") + "\n"
+}
+
+/// Translate a line outside of any block.
+fn translate_line(line: &str) -> String {
+    let trimmed = line.trim_start();
+    let indent = &line[..line.len() - trimmed.len()];
+
+    // Headings
+    let stars = trimmed.len() - trimmed.trim_start_matches('*').len();
+    if stars > 0 && indent.is_empty() && trimmed[stars..].starts_with(' ') {
+        let title = strip_tags(trimmed[stars..].trim());
+        return format!("{} {}", "#".repeat(stars.min(6)), translate_inline(title));
+    }
+
+    // Horizontal rules
+    if trimmed.len() >= 5 && trimmed.chars().all(|c| c == '-') {
+        return "---".into();
+    }
+
+    // Footnote definitions
+    if let Some(rest) = line.strip_prefix("[fn:") {
+        if let Some((label, text)) = rest.split_once(']') {
+            return format!("[^{label}]: {}", translate_inline(text.trim()));
+        }
+    }
+
+    // List items
+    for bullet in ["- ", "+ "] {
+        if let Some(item) = trimmed.strip_prefix(bullet) {
+            let item = match item.split_once(" :: ") {
+                Some((term, desc)) => {
+                    format!("**{}**: {}", translate_inline(term), translate_inline(desc))
+                }
+                None => translate_inline(item),
+            };
+            return format!("{indent}- {item}");
+        }
+    }
+    let digits = trimmed.len()
+        - trimmed
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .len();
+    if digits > 0 {
+        if let Some(item) = trimmed[digits..]
+            .strip_prefix(". ")
+            .or_else(|| trimmed[digits..].strip_prefix(") "))
+        {
+            return format!("{indent}{}. {}", &trimmed[..digits], translate_inline(item));
+        }
+    }
+
+    // Hard line breaks
+    match trimmed.trim_end().strip_suffix("\\\\") {
+        Some(rest) => format!("{}\\", translate_inline(rest)),
+        None => translate_inline(trimmed),
+    }
+}
+
+/// Remove tags like `:foo:bar:` from the end of a heading.
+fn strip_tags(title: &str) -> &str {
+    match title.rsplit_once(char::is_whitespace) {
+        Some((rest, tags))
+            if tags.len() > 2
+                && tags.starts_with(':')
+                && tags.ends_with(':')
+                && !tags.contains(char::is_whitespace) =>
+        {
+            rest.trim_end()
+        }
+        _ => title,
+    }
+}
+
+/// If the line starts a display math block, how much of it is the opening
+/// delimiter and what the block ends with.
+///
+/// Environments like `\begin{align}` are kept, since KaTeX understands them.
+fn display_math_start(line: &str) -> Option<(usize, String)> {
+    if line.starts_with("\\[") {
+        return Some((2, "\\]".into()));
+    }
+    if line.starts_with("$$") {
+        return Some((2, "$$".into()));
+    }
+    let env = line.strip_prefix("\\begin{")?.split_once('}')?.0;
+    Some((0, format!("\\end{{{env}}}")))
+}
+
+fn table_cells(row: &str) -> Vec<&str> {
+    let row = row.trim().trim_start_matches('|');
+    let row = row.strip_suffix('|').unwrap_or(row);
+    row.split('|').map(str::trim).collect()
+}
+
+fn table_row(cells: &[&str]) -> String {
+    let cells = cells
+        .iter()
+        .map(|c| translate_inline(c).replace('|', "\\|"))
+        .collect::<Vec<_>>();
+    format!("| {} |", cells.join(" | "))
+}
+
+/// Emphasis markers, and what they become in markdown.
+fn emphasis(marker: char) -> Option<(&'static str, &'static str)> {
+    Some(match marker {
+        '*' => ("**", "**"),
+        '/' => ("*", "*"),
+        '_' => ("<u>", "</u>"),
+        '+' => ("~~", "~~"),
+        '=' | '~' => ("`", "`"),
+        _ => return None,
+    })
+}
+
+/// Translate inline markup, like emphasis, links and math.
+// `Option::is_none_or` is newer than the toolchain the flake pins
+#[allow(clippy::unnecessary_map_or)]
+fn translate_inline(text: &str) -> String {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut out = String::new();
+    let mut i = 0;
+
+    let at = |i: usize, pat: &str| chars[i..].iter().copied().take(pat.len()).eq(pat.chars());
+    let find = |from: usize, pat: &str| (from..chars.len()).find(|&j| at(j, pat));
+
+    while i < chars.len() {
+        let c = chars[i];
+        let prev = if i == 0 { None } else { Some(chars[i - 1]) };
+
+        // Links
+        if at(i, "[[") {
+            if let Some(end) = find(i, "]]") {
+                let inner = chars[i + 2..end].iter().collect::<String>();
+                out.push_str(&translate_link(&inner));
+                i = end + 2;
+                continue;
+            }
+        }
+
+        // Footnote references
+        if at(i, "[fn:") {
+            if let Some(end) = find(i, "]") {
+                let label = chars[i + 4..end].iter().collect::<String>();
+                if !label.is_empty() && !label.contains(':') {
+                    out.push_str(&format!("[^{label}]"));
+                    i = end + 1;
+                    continue;
+                }
+            }
+        }
+
+        // Export snippets, passed straight through if they're for HTML
+        if at(i, "@@html:") {
+            if let Some(end) = find(i + 7, "@@") {
+                out.extend(&chars[i + 7..end]);
+                i = end + 2;
+                continue;
+            }
+        }
+
+        // Inline math
+        let delimited = [
+            ("\\(", "\\)", "<m>", "</m>"),
+            ("\\[", "\\]", "<M>", "</M>"),
+            ("$$", "$$", "<M>", "</M>"),
+        ]
+        .into_iter()
+        .find(|(open, ..)| at(i, open));
+        if let Some((_, close, tag, end_tag)) = delimited {
+            if let Some(end) = find(i + 2, close) {
+                let math = chars[i + 2..end].iter().collect::<String>();
+                out.push_str(&format!("{tag}{}{end_tag}", escape_math(&math)));
+                i = end + 2;
+                continue;
+            }
+        }
+        if c == '$'
+            && chars
+                .get(i + 1)
+                .is_some_and(|n| !n.is_whitespace() && *n != '$')
+        {
+            let end = (i + 1..chars.len()).find(|&j| {
+                chars[j] == '$'
+                    && !chars[j - 1].is_whitespace()
+                    && !chars.get(j + 1).is_some_and(|n| n.is_alphanumeric())
+            });
+            if let Some(end) = end {
+                let math = chars[i + 1..end].iter().collect::<String>();
+                out.push_str(&format!("<m>{}</m>", escape_math(&math)));
+                i = end + 1;
+                continue;
+            }
+        }
+
+        // Emphasis
+        if let Some((open, close)) = emphasis(c) {
+            let starts = prev.map_or(true, |p| p.is_whitespace() || "-({'\"".contains(p))
+                && chars.get(i + 1).is_some_and(|n| !n.is_whitespace());
+            let end = (i + 2..chars.len()).find(|&j| {
+                chars[j] == c
+                    && !chars[j - 1].is_whitespace()
+                    && chars
+                        .get(j + 1)
+                        .map_or(true, |n| n.is_whitespace() || "-.,;:!?')}[\"".contains(*n))
+            });
+            if let (true, Some(end)) = (starts, end) {
+                let body = chars[i + 1..end].iter().collect::<String>();
+                if open == "`" {
+                    out.push_str(&code_span(&body));
+                } else {
+                    out.push_str(&format!("{open}{}{close}", translate_inline(&body)));
+                }
+                i = end + 1;
+                continue;
+            }
+        }
+
+        // Anything else that markdown would treat as syntax
+        push_escaped(&mut out, c);
+        i += 1;
+    }
+
+    out
+}
+
+/// Translate the inside of a `[[link]]` or `[[link][description]]`.
+fn translate_link(inner: &str) -> String {
+    let (target, desc) = match inner.split_once("][") {
+        Some((t, d)) => (t, Some(d)),
+        None => (inner, None),
+    };
+    let target = link_target(target);
+
+    match desc {
+        // An image as the description makes a linked image
+        Some(d) if is_image(&link_target(d)) => {
+            format!("[![]({})]({target})", link_target(d))
+        }
+        Some(d) => format!("[{}]({target})", translate_inline(d)),
+        None if is_image(&target) => format!("![]({target})"),
+        None => format!("[{}]({target})", escape_link_text(&target)),
+    }
+}
+
+/// Turn an Org link target into a URL. Relative files are made explicitly
+/// relative, so that local images get relinked.
+fn link_target(target: &str) -> String {
+    let target = target.trim();
+    let file = target.strip_prefix("file:");
+    let path = file.unwrap_or(target);
+    if is_bare_relative(path) && (file.is_some() || is_image(path)) {
+        format!("./{path}")
+    } else {
+        path.replace(' ', "%20")
+    }
+}
+
+fn escape_link_text(text: &str) -> String {
+    text.replace('[', "\\[").replace(']', "\\]")
+}
+
+#[cfg(test)]
+mod tests {
+    use vfs::{MemoryFS, VfsPath};
+
+    use crate::{media::MediaRegistry, transform::links::LinkTargets};
+
+    use super::*;
+
+    #[test]
+    pub fn translates_org_to_markdown() {
+        let org = r#"#+TITLE: Notes
+* Intro :draft:
+Some *bold*, /italic/, _under_, +gone+, =x*y= and ~code~. 2 * 3 = 6.
+See [[https://example.com][the /site/]] and [[./cat.png]].[fn:1]
+
+** Math
+Inline $e^{i\pi}$ and \(a_1 + b_1\).
+\[
+x = \frac{1}{2}
+\]
+
+#+BEGIN_SRC rust :results output
+fn main() {}
+,* not a heading
+#+END_SRC
+
+- item one
+- term :: meaning
+1) first
+
+| a | b |
+|---+---|
+| 1 | x_y |
+
+[fn:1] A footnote.
+"#;
+
+        let md = org_to_markdown(org);
+
+        assert_eq!(
+            md,
+            r#"
+# Intro
+Some **bold**, *italic*, <u>under</u>, ~~gone~~, `x*y` and `code`. 2 \* 3 = 6.
+See [the *site*](https://example.com) and ![](./cat.png).[^1]
+
+## Math
+Inline <m>e\^\{i\\pi\}</m> and <m>a\_1 \+ b\_1</m>.
+
+
+<M>x \= \\frac\{1\}\{2\}</M>
+
+```rust
+fn main() {}
+* not a heading
+```
+
+- item one
+- **term**: meaning
+1. first
+
+| a | b |
+|---|---|
+| 1 | x\_y |
+
+[^1]: A footnote.
+"#
+        );
+        assert_eq!(md.lines().count(), org.lines().count());
+    }
+
+    #[test]
+    pub fn translates_dot_blocks_and_environments() {
+        let org = "#+begin_src dot\ndigraph { a -> b }\n#+end_src\n\\begin{align}\na &= b\n\\end{align}\n";
+
+        assert_eq!(
+            org_to_markdown(org),
+            "```dot\ndigraph { a -> b }\n```\n<M>\\\\begin\\{align\\}\na \\&\\= b\n\\\\end\\{align\\}</M>\n"
+        );
+    }
+
+    #[tokio::test]
+    pub async fn relinks_images_like_markdown() {
+        let root = VfsPath::new(MemoryFS::new());
+        root.join("cat.png")
+            .unwrap()
+            .create_file()
+            .unwrap()
+            .write_all(b"meow")
+            .unwrap();
+        let media = MediaRegistry::new("/static".into(), VfsPath::new(MemoryFS::new()));
+        let links = LinkTargets::default();
+        let ctx = TransformContext::new(root, &media, &links);
+
+        let out = transform_org(&ctx, "* Cat\n[[file:cat.png]]\n")
+            .await
+            .unwrap();
+
+        assert!(out.html.contains("<h1>"), "html = {}", out.html);
+        assert!(out.html.contains("/static/"), "html = {}", out.html);
+        assert_eq!(out.stats.images, 1);
+    }
+}
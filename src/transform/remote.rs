@@ -0,0 +1,318 @@
+use std::{collections::HashMap, io::Read, time::Duration};
+
+use comrak::nodes::{AstNode, NodeValue};
+use itertools::Itertools;
+use reqwest::header::CONTENT_TYPE;
+use tracing::{debug, warn};
+use vfs::VfsPath;
+
+use crate::{
+    errors::Errors,
+    media::{content_hash, Media, MediaRegistry},
+};
+
+use super::{
+    common::TransformContext,
+    markdown::{MarkdownError, MarkdownErrorKind},
+};
+
+/// Downloads remote images so they can be hosted alongside everything else,
+/// keeping a copy of each one on disk.
+pub struct RemoteImages {
+    /// Where downloaded images are kept, as `<sha256 of url>/<filename>`.
+    cache: VfsPath,
+
+    /// If set, only use what's already in the cache.
+    offline: bool,
+
+    client: reqwest::Client,
+}
+
+/// How long to wait for a download before giving up on it.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait for a host to accept a connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl RemoteImages {
+    pub fn new(cache: VfsPath, offline: bool) -> anyhow::Result<Self> {
+        Self::with_timeout(cache, offline, DOWNLOAD_TIMEOUT)
+    }
+
+    /// Like [RemoteImages::new], but giving up on downloads after `timeout`.
+    pub fn with_timeout(cache: VfsPath, offline: bool, timeout: Duration) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT.min(timeout))
+            .timeout(timeout)
+            .build()?;
+        Ok(Self {
+            cache,
+            offline,
+            client,
+        })
+    }
+
+    /// Get a remote image, from the cache if possible. Only images that have
+    /// never been downloaded are fetched, so a host that is down or times out
+    /// doesn't matter once an image is cached.
+    ///
+    /// Returns [None] if offline and the image has never been downloaded.
+    #[tracing::instrument(skip(self))]
+    pub async fn fetch(&self, url: &str) -> anyhow::Result<Option<Media>> {
+        let dir = self.cache.join(content_hash(url.as_bytes()))?;
+
+        if dir.is_dir()? {
+            if let Some(path) = dir.read_dir()?.next() {
+                debug!("using cached image");
+                let mut body = vec![];
+                path.open_file()?.read_to_end(&mut body)?;
+                return Ok(Some(Media {
+                    mimetype: mime_guess::from_path(path.filename()).first(),
+                    filename: Some(path.filename()),
+                    body,
+                }));
+            }
+        }
+
+        if self.offline {
+            warn!("image is not cached, leaving it remote");
+            return Ok(None);
+        }
+
+        debug!("downloading image");
+        let response = self.client.get(url).send().await?.error_for_status()?;
+        let mimetype = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.parse().ok());
+        let body = response.bytes().await?.to_vec();
+
+        let mut filename = filename_from_url(url);
+        if mime_guess::from_path(&filename).first().is_none() {
+            let ext = mimetype
+                .as_ref()
+                .and_then(|m| mime_guess::get_mime_extensions(m))
+                .and_then(|e| e.first());
+            if let Some(ext) = ext {
+                filename = format!("{filename}.{ext}");
+            }
+        }
+
+        dir.create_dir_all()?;
+        dir.join(&filename)?.create_file()?.write_all(&body)?;
+
+        Ok(Some(Media {
+            mimetype: mime_guess::from_path(&filename).first().or(mimetype),
+            filename: Some(filename),
+            body,
+        }))
+    }
+}
+
+/// Download images that aren't part of any content, like the ones templates
+/// use, and host them with the rest of the media.
+///
+/// Returns the new URL of each image that could be mirrored. Images that
+/// couldn't be are left remote, since templates have nowhere to report errors.
+pub async fn mirror_image_urls(media: &MediaRegistry, urls: &[&str]) -> HashMap<String, String> {
+    let Some(remote) = media.remote() else {
+        return HashMap::new();
+    };
+
+    let mut mirrored = HashMap::new();
+    for url in urls {
+        let result = match remote.fetch(url).await {
+            Ok(Some(m)) => media.upload_media(m).map(Some),
+            r => r.map(|_| None),
+        };
+        match result {
+            Ok(Some(new_url)) => {
+                mirrored.insert(url.to_string(), new_url);
+            }
+            Ok(None) => (),
+            Err(error) => warn!(url, %error, "Could not mirror image, leaving it remote"),
+        }
+    }
+    mirrored
+}
+
+/// A safe filename for something downloaded from the URL.
+fn filename_from_url(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let name = path
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        .collect::<String>();
+
+    match name.trim_start_matches('.') {
+        "" => "image".into(),
+        n => n.into(),
+    }
+}
+
+fn is_remote(url: &str) -> bool {
+    let url = url.trim();
+    url.starts_with("https://") || url.starts_with("http://")
+}
+
+/// Download remote images, and host them with the rest of the media.
+///
+/// Does nothing unless the media registry has been set up with [RemoteImages].
+#[tracing::instrument(skip_all)]
+pub async fn mirror_remote_images<'a>(
+    ctx: &TransformContext<'_>,
+    root: &'a AstNode<'a>,
+) -> Result<(), Errors<MarkdownError>> {
+    let Some(remote) = ctx.media().remote() else {
+        return Ok(());
+    };
+    let mut errors = Errors::new();
+
+    let images = root
+        .descendants()
+        .filter_map(|n| {
+            let ast = n.data.borrow();
+            match &ast.value {
+                NodeValue::Image(l) if is_remote(&l.url) => Some((n, l.url.clone(), ast.sourcepos)),
+                _ => None,
+            }
+        })
+        .collect_vec();
+
+    for (n, url, sourcepos) in images {
+        let result = match remote.fetch(url.trim()).await {
+            Ok(Some(media)) => ctx.media().upload_media(media).map(Some),
+            r => r.map(|_| None),
+        };
+
+        match result {
+            Ok(Some(new_url)) => {
+                if let NodeValue::Image(l) = &mut n.data.borrow_mut().value {
+                    l.url = new_url;
+                }
+            }
+            Ok(None) => (),
+            Err(e) => errors.push(MarkdownError::new(sourcepos, MarkdownErrorKind::Image(e))),
+        }
+    }
+
+    errors.into_result()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use comrak::{format_html, parse_document, Arena};
+    use vfs::MemoryFS;
+
+    use crate::{
+        media::MediaRegistry,
+        transform::{links::LinkTargets, markdown::make_md_options},
+    };
+
+    use super::*;
+
+    #[test]
+    pub fn filenames_are_sanitized() {
+        assert_eq!(
+            filename_from_url("https://example.com/a/under-construction.gif?v=2"),
+            "under-construction.gif"
+        );
+        assert_eq!(filename_from_url("https://example.com/"), "image");
+        assert_eq!(filename_from_url("https://example.com/../..%2f"), "2f");
+    }
+
+    #[tokio::test]
+    pub async fn offline_mode_uses_only_the_cache() {
+        let cached_url = "https://example.com/cat.png";
+        let cache = VfsPath::new(MemoryFS::new());
+        let dir = cache.join(content_hash(cached_url.as_bytes())).unwrap();
+        dir.create_dir_all().unwrap();
+        dir.join("cat.png")
+            .unwrap()
+            .create_file()
+            .unwrap()
+            .write_all(b"meow")
+            .unwrap();
+
+        let media = MediaRegistry::new("/static".into(), VfsPath::new(MemoryFS::new()))
+            .with_remote(RemoteImages::new(cache, true).unwrap());
+        let links = LinkTargets::default();
+        let ctx = TransformContext::new(VfsPath::new(MemoryFS::new()), &media, &links);
+
+        let arena = Arena::new();
+        let options = make_md_options();
+        let md = "![cat](https://example.com/cat.png) ![dog](https://unreachable.invalid/dog.png)";
+        let root = parse_document(&arena, md, &options);
+
+        mirror_remote_images(&ctx, root).await.unwrap();
+
+        let mut html = vec![];
+        format_html(root, &options, &mut html).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        let expected = format!(r#"src="/static/{}/cat.png""#, content_hash(b"meow"));
+        assert!(html.contains(&expected), "html = {html}");
+        assert!(
+            html.contains(r#"src="https://unreachable.invalid/dog.png""#),
+            "html = {html}"
+        );
+    }
+
+    #[tokio::test]
+    pub async fn stalled_hosts_time_out_but_cached_images_still_work() {
+        // Accepts connections, but never responds
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || listener.incoming().collect::<Vec<_>>());
+
+        let cached_url = format!("http://{addr}/cat.png");
+        let cache = VfsPath::new(MemoryFS::new());
+        let dir = cache.join(content_hash(cached_url.as_bytes())).unwrap();
+        dir.create_dir_all().unwrap();
+        dir.join("cat.png")
+            .unwrap()
+            .create_file()
+            .unwrap()
+            .write_all(b"meow")
+            .unwrap();
+        let remote = RemoteImages::with_timeout(cache, false, Duration::from_millis(200)).unwrap();
+
+        let stalled = remote.fetch(&format!("http://{addr}/dog.png")).await;
+        let cached = remote.fetch(&cached_url).await.unwrap().unwrap();
+
+        assert!(stalled.is_err());
+        assert_eq!(cached.body, b"meow");
+    }
+
+    #[tokio::test]
+    pub async fn template_images_are_left_remote_if_they_cant_be_mirrored() {
+        let cached_url = "https://example.com/banner.gif";
+        let cache = VfsPath::new(MemoryFS::new());
+        let dir = cache.join(content_hash(cached_url.as_bytes())).unwrap();
+        dir.create_dir_all().unwrap();
+        dir.join("banner.gif")
+            .unwrap()
+            .create_file()
+            .unwrap()
+            .write_all(b"gif")
+            .unwrap();
+        let media = MediaRegistry::new("/static".into(), VfsPath::new(MemoryFS::new()))
+            .with_remote(RemoteImages::new(cache, true).unwrap());
+
+        let mirrored =
+            mirror_image_urls(&media, &[cached_url, "https://unreachable.invalid/x.png"]).await;
+
+        assert_eq!(
+            mirrored,
+            HashMap::from([(
+                cached_url.to_string(),
+                format!("/static/{}/banner.gif", content_hash(b"gif"))
+            )])
+        );
+    }
+}
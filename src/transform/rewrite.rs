@@ -0,0 +1,309 @@
+use std::{
+    collections::HashMap,
+    ops::{Range, RangeInclusive},
+};
+
+use comrak::{
+    nodes::{AstNode, NodeValue},
+    parse_document, Arena,
+};
+
+use crate::load::util::split_frontmatter;
+
+use super::markdown::{is_local_image, make_md_options};
+
+/// A local image referenced from a markdown file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LocalImage {
+    /// The URL, as written.
+    pub url: String,
+
+    /// Lines of the block the image is in, 1-indexed.
+    pub lines: RangeInclusive<usize>,
+}
+
+/// Find every image in a markdown file that would get uploaded during a build.
+///
+/// The file may have YAML frontmatter, which is skipped.
+pub fn find_local_images(raw: &str) -> Vec<LocalImage> {
+    let (frontmatter, body) = split_frontmatter(raw);
+    let offset = frontmatter.matches('\n').count();
+
+    let arena = Arena::new();
+    let root: &AstNode = parse_document(&arena, body, &make_md_options());
+
+    root.descendants()
+        .filter_map(|n| {
+            let url = match &n.data.borrow().value {
+                NodeValue::Image(link) if is_local_image(&link.url) => link.url.clone(),
+                _ => return None,
+            };
+
+            // Positions of inlines aren't reliable, but those of their blocks are
+            let block = n.ancestors().find(|a| a.data.borrow().value.block())?;
+            let pos = block.data.borrow().sourcepos;
+            Some(LocalImage {
+                url,
+                lines: offset + pos.start.line..=offset + pos.end.line,
+            })
+        })
+        .collect()
+}
+
+/// Replace the URLs of the given images, leaving everything else in the file
+/// exactly as it was.
+///
+/// Also returns the images whose URLs couldn't be found in the source, and so
+/// weren't rewritten.
+pub fn rewrite_image_urls<'a>(
+    raw: &str,
+    images: &'a [LocalImage],
+    urls: &HashMap<String, String>,
+) -> (String, Vec<&'a LocalImage>) {
+    let line_starts = std::iter::once(0)
+        .chain(raw.match_indices('\n').map(|(i, _)| i + 1))
+        .collect::<Vec<_>>();
+    let body_start = split_frontmatter(raw).0.len();
+
+    let mut edits: Vec<(Range<usize>, &str)> = vec![];
+    let mut skipped = vec![];
+    for image in images {
+        let Some(new) = urls.get(&image.url) else {
+            continue;
+        };
+        let block = line_starts[image.lines.start() - 1]
+            ..line_starts
+                .get(*image.lines.end())
+                .copied()
+                .unwrap_or(raw.len());
+
+        // The image might use a link reference definition instead, like `[foo]: ./foo.png`
+        let found = inline_image_destinations(raw, block)
+            .into_iter()
+            .chain(reference_destinations(raw, body_start))
+            .find(|d| d.url == image.url && !edits.iter().any(|(span, _)| *span == d.span));
+        match found {
+            Some(d) => edits.push((d.span, new)),
+            None => skipped.push(image),
+        }
+    }
+
+    let mut out = raw.to_owned();
+    edits.sort_by_key(|(span, _)| std::cmp::Reverse(span.start));
+    for (span, new) in edits {
+        match new.contains(char::is_whitespace) {
+            true => out.replace_range(span, &format!("<{new}>")),
+            false => out.replace_range(span, new),
+        }
+    }
+
+    (out, skipped)
+}
+
+/// Where a link destination is in markdown source, and the URL it means.
+struct Destination {
+    /// Byte range of the destination, including any angle brackets.
+    span: Range<usize>,
+
+    /// The URL with escapes removed, the way comrak reads it.
+    url: String,
+}
+
+/// Destinations of the inline images like `![alt](dest)` in part of a file.
+fn inline_image_destinations(raw: &str, range: Range<usize>) -> Vec<Destination> {
+    let mut out = vec![];
+    let mut i = range.start;
+    while let Some(found) = raw[i..range.end].find("![") {
+        let open = i + found + 1;
+        i = open + 1;
+        if is_escaped(raw, open - 1) {
+            continue;
+        }
+        let Some(close) = matching_bracket(&raw[..range.end], open) else {
+            continue;
+        };
+        if raw[close + 1..].starts_with('(') {
+            out.extend(parse_destination(raw, close + 2).filter(|d| d.span.end <= range.end));
+        }
+    }
+    out
+}
+
+/// Destinations of link reference definitions like `[foo]: dest`.
+fn reference_destinations(raw: &str, from: usize) -> impl Iterator<Item = Destination> + '_ {
+    raw[from..].match_indices('\n').filter_map(move |(i, _)| {
+        let start = from + i + 1;
+        let line = &raw[start..];
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        if indent > 3 || !line[indent..].starts_with('[') {
+            return None;
+        }
+        let close = matching_bracket(raw, start + indent)?;
+        raw[close + 1..]
+            .starts_with(':')
+            .then(|| parse_destination(raw, close + 2))
+            .flatten()
+    })
+}
+
+/// Parse the link destination at `start`, after any whitespace.
+fn parse_destination(raw: &str, start: usize) -> Option<Destination> {
+    let rest = &raw[start..];
+    let start = start + rest.len() - rest.trim_start().len();
+    let rest = &raw[start..];
+    let angle = rest.starts_with('<');
+
+    let mut url = String::new();
+    let mut depth = 0;
+    let mut end = rest.len();
+    let mut chars = rest.char_indices().skip(angle as usize).peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if chars.peek().is_some_and(|(_, e)| e.is_ascii_punctuation()) => {
+                url.push(chars.next().unwrap().1);
+            }
+            '>' if angle => {
+                return Some(Destination {
+                    span: start..start + i + 1,
+                    url,
+                })
+            }
+            '<' | '\n' if angle => return None,
+            _ if angle => url.push(c),
+            '(' => {
+                depth += 1;
+                url.push(c);
+            }
+            ')' if depth > 0 => {
+                depth -= 1;
+                url.push(c);
+            }
+            ')' => {
+                end = i;
+                break;
+            }
+            c if c.is_whitespace() => {
+                end = i;
+                break;
+            }
+            c => url.push(c),
+        }
+    }
+
+    (!angle && !url.is_empty()).then(|| Destination {
+        span: start..start + end,
+        url,
+    })
+}
+
+/// Index of the `]` closing the `[` at `open`, if there is one.
+fn matching_bracket(raw: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut escaped = false;
+    for (i, c) in raw[open..].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '[' => depth += 1,
+            ']' if depth == 1 => return Some(open + i),
+            ']' => depth -= 1,
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Whether the character at `i` is escaped with a backslash.
+fn is_escaped(raw: &str, i: usize) -> bool {
+    raw[..i].chars().rev().take_while(|c| *c == '\\').count() % 2 == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POST: &str = r#"---
+title: Pictures
+thumbnail: ./thumb.png
+---
+
+Here is ![a cat](./cat.png "Cat") and ![a dog][dog],
+and a ![remote one](https://example.com/x.png).
+
+```md
+![not an image](./cat.png)
+```
+
+[dog]: ./dog.jpg
+"#;
+
+    #[test]
+    pub fn finds_local_images() {
+        let images = find_local_images(POST);
+
+        assert_eq!(
+            images,
+            vec![
+                LocalImage {
+                    url: "./cat.png".into(),
+                    lines: 6..=7
+                },
+                LocalImage {
+                    url: "./dog.jpg".into(),
+                    lines: 6..=7
+                },
+            ]
+        );
+    }
+
+    #[test]
+    pub fn rewrites_only_image_urls() {
+        let urls = HashMap::from([
+            ("./cat.png".to_string(), "https://cdn/a/cat.png".to_string()),
+            ("./dog.jpg".to_string(), "https://cdn/b/dog.jpg".to_string()),
+        ]);
+
+        let images = find_local_images(POST);
+
+        let (rewritten, skipped) = rewrite_image_urls(POST, &images, &urls);
+
+        let expected = POST
+            .replace("(./cat.png \"Cat\")", "(https://cdn/a/cat.png \"Cat\")")
+            .replace("[dog]: ./dog.jpg", "[dog]: https://cdn/b/dog.jpg");
+        assert_eq!(rewritten, expected);
+        assert!(rewritten.starts_with("---\ntitle: Pictures\nthumbnail: ./thumb.png\n---\n"));
+        assert!(skipped.is_empty());
+    }
+
+    #[test]
+    pub fn rewrites_only_the_image_destination() {
+        let post = "See [the cat](./cat.png), ![a cat](./cat.png) and ![me](<./my cat.png>).\n\n![sp\\]arkles](./sparkles\\(1\\).gif) ![cafe](./caf&eacute;.png)\n";
+        let urls = HashMap::from([
+            ("./cat.png".to_string(), "https://cdn/a/cat.png".to_string()),
+            (
+                "./my cat.png".to_string(),
+                "https://cdn/b/my cat.png".to_string(),
+            ),
+            (
+                "./sparkles(1).gif".to_string(),
+                "https://cdn/c/s.gif".to_string(),
+            ),
+            (
+                "./café.png".to_string(),
+                "https://cdn/d/cafe.png".to_string(),
+            ),
+        ]);
+        let images = find_local_images(post);
+
+        let (rewritten, skipped) = rewrite_image_urls(post, &images, &urls);
+
+        assert_eq!(
+            rewritten,
+            "See [the cat](./cat.png), ![a cat](https://cdn/a/cat.png) and ![me](<https://cdn/b/my cat.png>).\n\n![sp\\]arkles](https://cdn/c/s.gif) ![cafe](./caf&eacute;.png)\n"
+        );
+        assert_eq!(
+            skipped.iter().map(|i| i.url.as_str()).collect::<Vec<_>>(),
+            vec!["./café.png"]
+        );
+    }
+}
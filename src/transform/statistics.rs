@@ -1,9 +1,283 @@
-pub fn count_swears<'a>(text: &'a str) -> impl Iterator<Item = (&'static str, usize)> + 'a {
-    const SWEARS: &[&str] = &[
-        "fuck", "shit", "damn", "crap", "piss", "cunt", "cock", "tits",
-    ];
-
-    SWEARS
-        .into_iter()
-        .map(|swear| (*swear, text.matches(swear).count()))
+use std::{iter::Sum, time::Duration};
+
+use comrak::nodes::{AstNode, NodeValue};
+use vfs::VfsPath;
+
+use crate::model::TermCounter;
+
+use super::{gallery::expand_gallery, markdown::EmbedKind};
+
+/// Assumed reading speed of the average reader.
+pub const WORDS_PER_MINUTE: usize = 200;
+
+/// Assumed time spent looking at an image.
+pub const SECONDS_PER_IMAGE: usize = 12;
+
+/// Statistics about a single piece of content.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, derive_more::Add, derive_more::AddAssign)]
+pub struct DocumentStatistics {
+    /// Number of words of prose.
+    pub words: usize,
+
+    /// Number of code blocks, not counting ones that get turned into images or math.
+    pub code_blocks: usize,
+
+    /// Number of images, including generated ones like Graphviz diagrams.
+    pub images: usize,
+
+    /// Number of display-mode math blocks.
+    pub math_blocks: usize,
+
+    /// Number of embedded videos, which don't count towards reading time.
+    pub videos: usize,
+
+    /// Number of embedded audio clips, which don't count towards reading time.
+    pub audio: usize,
+}
+
+impl DocumentStatistics {
+    /// Statistics for a piece of plain text.
+    pub fn from_text(text: &str) -> Self {
+        Self {
+            words: count_words(text),
+            ..Default::default()
+        }
+    }
+
+    /// Statistics for a piece of HTML.
+    ///
+    /// This is a rough approximation that strips out tags and counts the
+    /// remaining words, and counts `<pre>` and `<img>` elements.
+    pub fn from_html(html: &str) -> Self {
+        Self {
+            words: count_words(&strip_tags(html)),
+            code_blocks: html.matches("<pre").count(),
+            images: html.matches("<img").count(),
+            math_blocks: html.matches("<M>").count(),
+            videos: html.matches("<video").count(),
+            audio: html.matches("<audio").count(),
+        }
+    }
+
+    /// Statistics for a markdown AST.
+    ///
+    /// This must be called before any transforms are applied, because they
+    /// replace code blocks and math with raw HTML. Galleries are expanded
+    /// relative to `content_root` to count their images.
+    pub fn from_markdown<'a>(content_root: &VfsPath, root: &'a AstNode<'a>) -> Self {
+        let mut stats = Self::default();
+
+        for n in root.descendants() {
+            match &n.data.borrow().value {
+                NodeValue::Text(t) => stats.words += count_words(t),
+                NodeValue::Code(c) => stats.words += count_words(&c.literal),
+                NodeValue::Image(l) => match EmbedKind::from_url(&l.url) {
+                    EmbedKind::Image => stats.images += 1,
+                    EmbedKind::Video => stats.videos += 1,
+                    EmbedKind::Audio => stats.audio += 1,
+                },
+                NodeValue::CodeBlock(cb) if cb.info == "math" => stats.math_blocks += 1,
+                NodeValue::CodeBlock(cb) if cb.info == "dot" || cb.info.starts_with("dot:") => {
+                    stats.images += 1
+                }
+                // Broken galleries get reported when they're rendered
+                NodeValue::CodeBlock(cb) if cb.info.trim() == "gallery" => {
+                    stats.images += expand_gallery(content_root, &cb.literal).map_or(0, |i| i.len())
+                }
+                NodeValue::CodeBlock(_) => stats.code_blocks += 1,
+                NodeValue::HtmlBlock(b) if b.literal.trim_start().starts_with("<M>") => {
+                    stats.math_blocks += 1
+                }
+                _ => (),
+            }
+        }
+
+        stats
+    }
+
+    /// Estimated time it takes to read the content.
+    pub fn reading_time(&self) -> Duration {
+        let seconds =
+            (self.words * 60).div_ceil(WORDS_PER_MINUTE) + self.images * SECONDS_PER_IMAGE;
+        Duration::from_secs(seconds as u64)
+    }
+
+    /// Estimated time it takes to read the content, rounded up to the minute.
+    pub fn reading_minutes(&self) -> u64 {
+        self.reading_time().as_secs().div_ceil(60).max(1)
+    }
+}
+
+impl Sum for DocumentStatistics {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |a, b| a + b)
+    }
+}
+
+impl<'a> Sum<&'a DocumentStatistics> for DocumentStatistics {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.copied().sum()
+    }
+}
+
+fn count_words(text: &str) -> usize {
+    text.split_whitespace()
+        .filter(|w| w.chars().any(|c| c.is_alphanumeric()))
+        .count()
+}
+
+/// Remove all tags from a piece of HTML, leaving only the text.
+///
+/// Every tag is replaced with a space so that words in adjacent elements
+/// don't get merged together.
+pub fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        text.push(' ');
+        rest = match rest[start..].find('>') {
+            Some(end) => &rest[start + end + 1..],
+            None => "",
+        };
+    }
+    text.push_str(rest);
+    text
+}
+
+/// Count the occurrences of each of the counter's terms in some text.
+///
+/// Only whole words are matched, so "scrap" does not count as "crap".
+pub fn count_terms<'a>(
+    text: &str,
+    counter: &'a TermCounter,
+) -> impl Iterator<Item = (&'a str, usize)> + 'a {
+    let text = match counter.case_sensitive {
+        true => text.to_owned(),
+        false => text.to_lowercase(),
+    };
+
+    counter.terms.iter().map(move |term| {
+        let (needle, prefix) = match term.strip_suffix('*') {
+            Some(t) => (t, true),
+            None => (term.as_str(), false),
+        };
+        let needle = match counter.case_sensitive {
+            true => needle.to_owned(),
+            false => needle.to_lowercase(),
+        };
+        (term.as_str(), count_word(&text, &needle, prefix))
+    })
+}
+
+fn count_word(text: &str, word: &str, prefix: bool) -> usize {
+    if word.is_empty() {
+        return 0;
+    }
+
+    let is_word_char = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+
+    text.match_indices(word)
+        .filter(|(i, _)| {
+            let before = text[..*i].chars().next_back();
+            let after = text[i + word.len()..].chars().next();
+            !is_word_char(before) && (prefix || !is_word_char(after))
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use comrak::{parse_document, Arena};
+    use vfs::MemoryFS;
+
+    use crate::transform::markdown::make_md_options;
+
+    use super::*;
+
+    #[test]
+    pub fn markdown_statistics_are_counted() {
+        let arena = Arena::new();
+        let md = r#"
+Some *words* in a `paragraph`.
+
+![an image](./foo.jpg) ![a clip](./clip.mp4) ![a song](./song.ogg)
+
+```rust
+fn main() {}
+```
+
+```math
+x^2
+```
+
+```dot
+digraph { a -> b }
+```
+
+```gallery
+./build/*.jpg
+```
+"#;
+        let content_root = VfsPath::new(MemoryFS::new());
+        content_root
+            .join("build")
+            .unwrap()
+            .create_dir_all()
+            .unwrap();
+        for name in ["build/1.jpg", "build/2.jpg"] {
+            content_root.join(name).unwrap().create_file().unwrap();
+        }
+        let root = parse_document(&arena, md, &make_md_options());
+
+        let stats = DocumentStatistics::from_markdown(&content_root, root);
+
+        assert_eq!(
+            stats,
+            DocumentStatistics {
+                words: 11,
+                code_blocks: 1,
+                images: 4,
+                math_blocks: 1,
+                videos: 1,
+                audio: 1,
+            }
+        );
+    }
+
+    #[test]
+    pub fn html_statistics_ignore_tags() {
+        let stats = DocumentStatistics::from_html(
+            r#"<p class="foo">hello <b>world</b></p><img src="a.png"><pre>code</pre>"#,
+        );
+
+        assert_eq!(stats.words, 3);
+        assert_eq!(stats.images, 1);
+        assert_eq!(stats.code_blocks, 1);
+    }
+
+    #[test]
+    pub fn term_counts_respect_word_boundaries() {
+        let counter = TermCounter {
+            id: "swears".into(),
+            title: "Swears".into(),
+            terms: vec!["crap".into(), "fuck*".into()],
+            case_sensitive: false,
+        };
+
+        let counts: Vec<_> = count_terms("Crap, scrap, crappy. Fucking fuck!", &counter).collect();
+
+        assert_eq!(counts, vec![("crap", 1), ("fuck*", 2)]);
+    }
+
+    #[test]
+    pub fn reading_time_rounds_up() {
+        let stats = DocumentStatistics {
+            words: WORDS_PER_MINUTE + 1,
+            ..Default::default()
+        };
+
+        assert_eq!(stats.reading_minutes(), 2);
+        assert_eq!(DocumentStatistics::default().reading_minutes(), 1);
+    }
 }
@@ -1,50 +1,452 @@
-use s3::{creds::Credentials, Bucket, Region};
-use sha2::{Digest, Sha256};
-use tracing::{debug, debug_span};
+use std::{future::Future, io::Read, time::Duration};
 
-use crate::media::Uploadable;
+use async_trait::async_trait;
+use futures::{stream, StreamExt, TryStreamExt};
+use s3::{creds::Credentials, error::S3Error, Bucket, Region};
+use serde::Serialize;
+use tokio::time::sleep;
+use tracing::{debug, debug_span, info, warn};
+use vfs::VfsPath;
 
-#[tracing::instrument(skip_all, fields(bucket_name))]
-pub async fn upload_to_s3(bucket_name: &str, media: impl Uploadable) -> anyhow::Result<String> {
-    let media = media.as_media()?;
-    let endpoint = "https://s3.us-west-000.backblazeb2.com".to_string();
+use crate::{
+    media::{content_hash, FileUploadable, Uploadable},
+    model::StorageSettings,
+};
+
+pub const DEFAULT_BUCKET: &str = "nyaabucket";
+pub const DEFAULT_ENDPOINT: &str = "https://s3.us-west-000.backblazeb2.com";
+pub const DEFAULT_REGION: &str = "us-west-1";
+pub const DEFAULT_PUBLIC_URL: &str = "{endpoint}/{bucket}/{key}";
+
+/// Maximum number of uploads in flight when publishing media.
+pub const MAX_CONCURRENT_UPLOADS: usize = 8;
+
+/// Fully-resolved configuration for an S3-compatible bucket.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct S3Config {
+    pub bucket: String,
+    pub endpoint: String,
+    pub region: String,
+    pub path_style: bool,
+    pub public_url: String,
+    pub key_prefix: String,
+}
+
+impl S3Config {
+    /// Fill in anything left unspecified with the defaults.
+    pub fn new(settings: &StorageSettings) -> Self {
+        let or = |s: &Option<String>, d: &str| s.clone().unwrap_or_else(|| d.to_owned());
+        Self {
+            bucket: or(&settings.bucket, DEFAULT_BUCKET),
+            endpoint: or(&settings.endpoint, DEFAULT_ENDPOINT)
+                .trim_end_matches('/')
+                .to_owned(),
+            region: or(&settings.region, DEFAULT_REGION),
+            path_style: settings.path_style.unwrap_or(false),
+            public_url: or(&settings.public_url, DEFAULT_PUBLIC_URL),
+            key_prefix: or(&settings.key_prefix, "").trim_matches('/').to_owned(),
+        }
+    }
+
+    /// Connect to the bucket, with credentials from the environment.
+    ///
+    /// Environment variables required: AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY
+    pub fn bucket(&self) -> anyhow::Result<Bucket> {
+        let region = Region::Custom {
+            region: self.region.clone(),
+            endpoint: self.endpoint.clone(),
+        };
+        let credentials = Credentials::default()?;
+
+        let bucket = Bucket::new(&self.bucket, region, credentials)?;
+        Ok(match self.path_style {
+            true => bucket.with_path_style(),
+            false => bucket,
+        })
+    }
+
+    /// The key to store an object at, after applying the prefix.
+    pub fn key(&self, path: &str) -> String {
+        match self.key_prefix.as_str() {
+            "" => path.to_owned(),
+            prefix => format!("{prefix}/{path}"),
+        }
+    }
+
+    /// The URL that every key gets appended to, if the public URL template
+    /// ends with `{key}`.
+    pub fn url_prefix(&self) -> String {
+        self.public_url(&self.key(""))
+            .trim_end_matches('/')
+            .to_owned()
+    }
+
+    /// The URL that an object can be publicly accessed at.
+    pub fn public_url(&self, key: &str) -> String {
+        self.public_url
+            .replace("{endpoint}", &self.endpoint)
+            .replace("{bucket}", &self.bucket)
+            .replace("{key}", key)
+    }
+}
+
+/// Somewhere that objects can be stored by key.
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    /// Fetch an object, or [None] if it does not exist.
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
+
+    async fn exists(&self, key: &str) -> anyhow::Result<bool>;
+
+    /// Every key starting with the given prefix.
+    async fn list_keys(&self, prefix: &str) -> anyhow::Result<Vec<String>>;
+
+    async fn put(&self, key: &str, body: &[u8], content_type: &str) -> anyhow::Result<()>;
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()>;
+}
+
+#[async_trait]
+impl ObjectStore for Bucket {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        match self.get_object(key).await {
+            Ok(r) => Ok(Some(r.to_vec())),
+            Err(S3Error::Http(404, _)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        match self.head_object(key).await {
+            Ok(_) => Ok(true),
+            Err(S3Error::Http(404, _)) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn list_keys(&self, prefix: &str) -> anyhow::Result<Vec<String>> {
+        let results = self.list(prefix.to_owned(), None).await?;
+        Ok(results
+            .into_iter()
+            .flat_map(|r| r.contents)
+            .map(|o| o.key)
+            .collect())
+    }
+
+    async fn put(&self, key: &str, body: &[u8], content_type: &str) -> anyhow::Result<()> {
+        self.put_object_with_content_type(key, body, content_type)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.delete_object(key).await?;
+        Ok(())
+    }
+}
+
+/// Stores objects as files in a directory, for testing without a real bucket.
+#[async_trait]
+impl ObjectStore for VfsPath {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let path = self.join(key)?;
+        if !path.is_file()? {
+            return Ok(None);
+        }
+        let mut buf = vec![];
+        path.open_file()?.read_to_end(&mut buf)?;
+        Ok(Some(buf))
+    }
+
+    async fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        Ok(self.join(key)?.is_file()?)
+    }
+
+    async fn list_keys(&self, prefix: &str) -> anyhow::Result<Vec<String>> {
+        let mut keys = vec![];
+        if !self.exists()? {
+            return Ok(keys);
+        }
+        for p in self.walk_dir()? {
+            let p = p?;
+            let key = p.as_str().strip_prefix(self.as_str()).unwrap_or(p.as_str());
+            let key = key.trim_start_matches('/');
+            if key.starts_with(prefix) && p.is_file()? {
+                keys.push(key.to_owned());
+            }
+        }
+        Ok(keys)
+    }
+
+    async fn put(&self, key: &str, body: &[u8], _content_type: &str) -> anyhow::Result<()> {
+        let path = self.join(key)?;
+        path.parent().create_dir_all()?;
+        path.create_file()?.write_all(body)?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.join(key)?.remove_file()?;
+        Ok(())
+    }
+}
 
-    let region = Region::Custom {
-        region: "us-west-1".to_string(),
-        endpoint: endpoint.clone(),
-    };
-    let credentials = Credentials::default()?;
+/// How often to retry failed requests, and how long to wait between them.
+#[derive(Clone, Copy, Debug)]
+pub struct Retries {
+    /// Number of retries after the first attempt.
+    pub retries: usize,
 
-    let bucket = Bucket::new(bucket_name, region, credentials)?;
+    /// Delay before the first retry, doubled after every attempt.
+    pub base_delay: Duration,
+}
 
-    let mut hasher = Sha256::new();
-    hasher.update(&media.body);
-    let hash = hasher.finalize();
-    let b16 = base16::encode_lower(&hash);
+impl Default for Retries {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            base_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl Retries {
+    pub async fn run<T, Fut>(&self, mut f: impl FnMut() -> Fut) -> anyhow::Result<T>
+    where
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let mut delay = self.base_delay;
+        for attempt in 0.. {
+            match f().await {
+                Err(error) if attempt < self.retries => {
+                    warn!(%error, attempt, ?delay, "Request failed, retrying");
+                    sleep(delay).await;
+                    delay *= 2;
+                }
+                r => return r,
+            }
+        }
+        unreachable!()
+    }
+}
 
-    debug!(filename = media.filename, size = media.body.len(), sha = %b16, "Uploadinng");
+/// The result of uploading a single piece of media.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Uploaded {
+    pub url: String,
+    pub sha256: String,
 
-    let path = match &media.filename {
-        Some(n) => format!("{}/{}", b16, n),
-        None => b16,
-    };
+    /// Whether the object already existed, so nothing had to be uploaded.
+    pub existed: bool,
+}
 
-    let content_type = media.mimetype.map(|m| m.essence_str().to_owned());
+/// Upload media to its content-addressed key, unless it already exists.
+#[tracing::instrument(skip_all, fields(bucket = config.bucket))]
+pub async fn upload_to_s3(
+    store: &impl ObjectStore,
+    config: &S3Config,
+    media: impl Uploadable,
+    retries: Retries,
+) -> anyhow::Result<Uploaded> {
+    let media = media.as_media()?;
+    let sha256 = content_hash(&media.body);
+    let path = config.key(&media.key());
+    let url = config.public_url(&path);
 
-    let url = format!("{endpoint}/{bucket_name}/{path}");
+    let content_type = media
+        .mimetype
+        .as_ref()
+        .map_or(mime::APPLICATION_OCTET_STREAM.essence_str(), |m| {
+            m.essence_str()
+        });
 
-    let span = debug_span!("uploading", size = media.body.len(), ?content_type, ?path, %url);
+    let span = debug_span!(
+        "uploading",
+        size = media.body.len(),
+        content_type,
+        path,
+        url
+    );
     let _enter = span.enter();
 
-    debug!("performing upload");
-    match content_type {
-        Some(ct) => {
-            bucket
-                .put_object_with_content_type(path.clone(), &media.body, &ct)
-                .await?
+    let existed = retries.run(|| store.exists(&path)).await?;
+    if existed {
+        debug!("already exists, skipping");
+    } else {
+        debug!("performing upload");
+        retries
+            .run(|| store.put(&path, &media.body, content_type))
+            .await?;
+    }
+
+    Ok(Uploaded {
+        url,
+        sha256,
+        existed,
+    })
+}
+
+/// Upload media collected during a build, skipping objects that already
+/// exist remotely. Keys are content-addressed, so existing objects never
+/// need to be replaced.
+///
+/// Returns the number of objects that were uploaded.
+#[tracing::instrument(skip_all, fields(count = files.len()))]
+pub async fn publish_media(
+    store: &impl ObjectStore,
+    config: &S3Config,
+    files: Vec<FileUploadable>,
+) -> anyhow::Result<usize> {
+    let uploaded = stream::iter(files)
+        .map(|f| upload_to_s3(store, config, f, Retries::default()))
+        .buffer_unordered(MAX_CONCURRENT_UPLOADS)
+        .try_fold(0, |n, u| async move { Ok(n + !u.existed as usize) })
+        .await?;
+
+    info!(uploaded, "Published media");
+    Ok(uploaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use frunk::Semigroup;
+    use vfs::MemoryFS;
+
+    use crate::media::{Media, MediaRegistry};
+
+    use super::*;
+
+    #[test]
+    pub fn defaults_match_backblaze() {
+        let config = S3Config::new(&StorageSettings::default());
+
+        assert_eq!(
+            config.public_url(&config.key("abc/cat.png")),
+            "https://s3.us-west-000.backblazeb2.com/nyaabucket/abc/cat.png"
+        );
+    }
+
+    #[test]
+    pub fn later_settings_take_precedence() {
+        let site = StorageSettings {
+            bucket: Some("site-bucket".into()),
+            endpoint: Some("http://localhost:9000/".into()),
+            key_prefix: Some("/media/".into()),
+            ..Default::default()
+        };
+        let cli = StorageSettings {
+            bucket: Some("cli-bucket".into()),
+            public_url: Some("https://cdn.example.com/{key}".into()),
+            ..Default::default()
+        };
+
+        let config = S3Config::new(&site.combine(&cli));
+
+        assert_eq!(config.bucket, "cli-bucket");
+        assert_eq!(config.endpoint, "http://localhost:9000");
+        assert_eq!(config.key("abc/cat.png"), "media/abc/cat.png");
+        assert_eq!(
+            config.public_url("media/abc/cat.png"),
+            "https://cdn.example.com/media/abc/cat.png"
+        );
+    }
+
+    #[tokio::test]
+    pub async fn publishes_only_missing_media() {
+        let config = S3Config::new(&StorageSettings {
+            public_url: Some("https://cdn.example.com/{key}".into()),
+            key_prefix: Some("media".into()),
+            ..Default::default()
+        });
+        let store = VfsPath::new(MemoryFS::new());
+        let media = MediaRegistry::new(config.url_prefix(), VfsPath::new(MemoryFS::new()));
+        let image = |body: &[u8]| Media {
+            filename: Some("image.png".into()),
+            mimetype: Some(mime::IMAGE_PNG),
+            body: body.to_vec(),
+        };
+
+        let url = media.upload_media(image(b"new")).unwrap();
+        // Used by another document too
+        assert_eq!(media.upload_media(image(b"new")).unwrap(), url);
+        media.upload_media(image(b"existing")).unwrap();
+        let existing_key = config.key(&image(b"existing").key());
+        store
+            .put(&existing_key, b"existing", "image/png")
+            .await
+            .unwrap();
+
+        let uploaded = publish_media(&store, &config, media.into_files())
+            .await
+            .unwrap();
+
+        let key = config.key(&image(b"new").key());
+        assert_eq!(uploaded, 1);
+        assert_eq!(url, format!("https://cdn.example.com/{key}"));
+        assert_eq!(store.get(&key).await.unwrap(), Some(b"new".to_vec()));
+    }
+
+    /// Fails the first few writes, like a flaky network would.
+    struct FlakyStore {
+        inner: VfsPath,
+        failures: std::sync::Mutex<usize>,
+    }
+
+    #[async_trait]
+    impl ObjectStore for FlakyStore {
+        async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+            self.inner.get(key).await
+        }
+
+        async fn exists(&self, key: &str) -> anyhow::Result<bool> {
+            ObjectStore::exists(&self.inner, key).await
+        }
+
+        async fn list_keys(&self, prefix: &str) -> anyhow::Result<Vec<String>> {
+            self.inner.list_keys(prefix).await
+        }
+
+        async fn put(&self, key: &str, body: &[u8], content_type: &str) -> anyhow::Result<()> {
+            {
+                let mut failures = self.failures.lock().unwrap();
+                if *failures > 0 {
+                    *failures -= 1;
+                    anyhow::bail!("connection reset");
+                }
+            }
+            self.inner.put(key, body, content_type).await
         }
-        None => bucket.put_object(path.clone(), &media.body).await?,
-    };
 
-    Ok(url)
+        async fn delete(&self, key: &str) -> anyhow::Result<()> {
+            self.inner.delete(key).await
+        }
+    }
+
+    #[tokio::test]
+    pub async fn upload_retries_and_skips_existing() {
+        let config = S3Config::new(&StorageSettings::default());
+        let store = FlakyStore {
+            inner: VfsPath::new(MemoryFS::new()),
+            failures: 2.into(),
+        };
+        let retries = Retries {
+            retries: 2,
+            base_delay: Duration::ZERO,
+        };
+        let media = Media {
+            filename: Some("cat.png".into()),
+            mimetype: Some(mime::IMAGE_PNG),
+            body: b"meow".to_vec(),
+        };
+
+        let first = upload_to_s3(&store, &config, media.clone(), retries)
+            .await
+            .unwrap();
+        let second = upload_to_s3(&store, &config, media, retries).await.unwrap();
+
+        assert!(!first.existed);
+        assert!(second.existed);
+        assert_eq!(first.url, second.url);
+        assert_eq!(first.sha256, content_hash(b"meow"));
+    }
 }
@@ -0,0 +1,22 @@
+use std::process::Command;
+
+#[test]
+pub fn check_json_output_is_only_diagnostics() {
+    let output = Command::new(env!("CARGO_BIN_EXE_seams"))
+        .args(["check", "--message-format", "json"])
+        .arg(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/test_data/astrid_dot_tech_example"
+        ))
+        .output()
+        .unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.trim().is_empty(), "expected some diagnostics");
+    for line in stdout.lines() {
+        let diagnostic: serde_json::Value = serde_json::from_str(line)
+            .unwrap_or_else(|e| panic!("stdout line is not JSON ({e}): {line:?}"));
+        assert!(diagnostic["message"].is_string(), "line = {line}");
+    }
+    assert!(!output.status.success());
+}